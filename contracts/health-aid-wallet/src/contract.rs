@@ -4,11 +4,16 @@
 //! Users can deposit USDC, create funds for specific procedures, and make payments
 //! only to approved healthcare providers.
 
+use protocol_config::{
+    FeeConfig, PendingAddressUpdate, ProtocolAddresses, ProtocolConfigClient, UPDATE_TIMELOCK,
+};
 use provider_registry::ProviderRegistryClient;
 use soroban_sdk::{
     auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation},
     contract, contracterror, contractimpl, contracttype, panic_with_error, symbol_short, token,
-    vec, Address, Env, IntoVal, InvokeError, Symbol, Val, Vec,
+    vec,
+    xdr::ToXdr,
+    Address, Bytes, BytesN, Env, IntoVal, InvokeError, String, Symbol, TryFromVal, Val, Vec,
 };
 
 // Storage keys
@@ -24,6 +29,363 @@ pub enum DataKey {
     RegistryAddress,
     UsdcToken,
     DefindexContract,
+    ProtocolConfig,
+    ConfigVersion,
+    HistoryCount,
+    History(u32),
+    PaymentMemo(u32),
+    Principal,
+    Shares,
+    YieldSnapshotCount,
+    YieldSnapshot(u32),
+    YieldDonationPolicy,
+    LastHarvest,
+    IdleOnly,
+    Frozen,
+    UnfreezeRequestedAt,
+    Closed,
+    LastOwnerActivity,
+    BeneficiaryPlan,
+    InheritanceRelease,
+    EscrowCount,
+    Escrow(u32),
+    EscrowedTotal,
+    HoldCount,
+    Hold(u32),
+    ActiveHolds,
+    RequestNonce(Address, u64),
+    ApprovalPolicy,
+    ProposalCount,
+    Proposal(u32),
+    AllowlistMode,
+    Favourite(Address),
+    Blocked(Address),
+    AddressOverride,
+    PendingConfig,
+    Contributions(u32),
+    RoundUpPolicy,
+    RoundUpDonated(u32, u32),
+    TotalDonated,
+    AuditHead,
+    HarvestedYield,
+}
+
+/// Maximum length of a payment memo's invoice reference, in bytes
+pub const MAX_INVOICE_REF_LEN: u32 = 64;
+
+/// Minimum time between two yield snapshots, in seconds
+pub const YIELD_SNAPSHOT_INTERVAL: u64 = 24 * 60 * 60;
+
+/// Denominator for percentages expressed in basis points
+pub const BPS_DENOMINATOR: i128 = 10_000;
+
+/// Time the owner must wait between requesting and completing an unfreeze
+/// without the guardian's co-signature, in seconds
+pub const UNFREEZE_DELAY: u64 = 2 * 24 * 60 * 60;
+
+/// Time the owner has to confirm or dispute an escrowed payment, in seconds
+pub const DISPUTE_WINDOW: u64 = 7 * 24 * 60 * 60;
+
+/// Shortest inactivity period a beneficiary plan can use, in seconds
+pub const MIN_INACTIVITY_PERIOD: u64 = 90 * 24 * 60 * 60;
+
+/// Largest round-up increment, 10 USDC in the token's 7-decimal units
+pub const MAX_ROUND_UP_INCREMENT: i128 = 10 * 10_000_000;
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransactionKind {
+    Deposit,
+    Payment,
+    Donation,
+    Fee,
+    Withdrawal,
+    Refund,
+}
+
+/// Reconciliation data attached to a payment, stored alongside its history entry.
+///
+/// Medical details never go on chain in plaintext: the procedure code and any
+/// free-text note are only stored as SHA-256 hashes computed off chain.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentMemo {
+    /// Provider invoice reference, at most `MAX_INVOICE_REF_LEN` bytes
+    pub invoice_ref: String,
+    /// Hash of the procedure code
+    pub procedure_hash: Option<BytesN<32>>,
+    /// Hash of a free-text note
+    pub note_hash: Option<BytesN<32>>,
+}
+
+/// A single entry of the wallet's transaction history.
+///
+/// `counterparty` is the funder for deposits, the provider for payments, the
+/// community recipient for donations, the treasury for platform fees and the
+/// provider for refunded escrowed payments.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransactionRecord {
+    pub kind: TransactionKind,
+    pub counterparty: Address,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EscrowStatus {
+    /// Held by the wallet until the owner confirms or the dispute window ends
+    Pending,
+    /// Disputed by the owner, waiting for the registry's arbiter
+    Disputed,
+    Released,
+    Refunded,
+}
+
+/// Payment held by the wallet until the service is confirmed.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EscrowPayment {
+    pub provider: Address,
+    pub amount: i128,
+    /// Platform fee withheld with the payment, collected on release
+    pub fee: i128,
+    /// Principal and harvested yield spent on the payment, restored on refund
+    pub cost_basis: i128,
+    pub harvested_yield: i128,
+    pub treasury: Option<Address>,
+    pub created_at: u64,
+    /// End of the dispute window, after which anyone can release the payment
+    pub release_after: u64,
+    pub status: EscrowStatus,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HoldStatus {
+    /// Reserving balance until captured, released or expired
+    Active,
+    Captured,
+    Released,
+}
+
+/// Pre-authorization placed by the owner for a provider, e.g. at hospital
+/// admission. An active hold past its expiry no longer reserves balance and
+/// cannot be captured.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hold {
+    pub provider: Address,
+    pub max_amount: i128,
+    /// Platform fee on `max_amount` when the hold was placed, reserved
+    /// alongside it so a full capture can pay the fee
+    pub fee: i128,
+    pub expiry: u64,
+    /// Amount captured by the provider, zero until captured
+    pub captured: i128,
+    pub status: HoldStatus,
+}
+
+/// Deposits made in one calendar year, split into the owner's own deposits
+/// and those of third-party funders.
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct YearContributions {
+    pub owner: i128,
+    pub third_party: i128,
+}
+
+/// Point-in-time view of the wallet's vault position split into principal and yield.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct YieldSnapshot {
+    pub timestamp: u64,
+    pub balance: i128,
+    pub principal: i128,
+    pub yield_earned: i128,
+}
+
+/// Payment request signed by a provider, e.g. encoded in a checkout QR code.
+///
+/// The provider signs the XDR encoding of the request with the ed25519 key
+/// registered in the provider registry. The request names the paying wallet,
+/// so a signed request cannot be replayed against other wallets.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentRequest {
+    pub provider: Address,
+    /// The wallet the request is addressed to
+    pub wallet: Address,
+    pub amount: i128,
+    /// Token the amount is denominated in, must be the wallet's USDC
+    pub currency: Address,
+    /// Provider invoice reference, at most `MAX_INVOICE_REF_LEN` bytes
+    pub invoice_ref: String,
+    /// Provider-chosen value making the request single-use
+    pub nonce: u64,
+    /// Time after which the request can no longer be paid
+    pub expiry: u64,
+}
+
+/// Co-signer policy for large payments.
+///
+/// Payments above `threshold_amount` must be proposed by the owner and
+/// approved by `required_approvals` of the `signers`, such as family members
+/// or a case manager.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApprovalPolicy {
+    pub threshold_amount: i128,
+    pub signers: Vec<Address>,
+    pub required_approvals: u32,
+    /// Time a proposal stays open for approvals and execution, in seconds
+    pub proposal_ttl: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProposalStatus {
+    Pending,
+    Executed,
+    Cancelled,
+}
+
+/// Large payment proposed by the owner, waiting for co-signer approvals.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentProposal {
+    pub destination: Address,
+    pub amount: i128,
+    pub approvals: Vec<Address>,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub status: ProposalStatus,
+}
+
+/// Address entitled to part of the wallet once the owner is inactive.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Beneficiary {
+    pub address: Address,
+    /// Share of the wallet balance, in basis points
+    pub share_bps: u32,
+}
+
+/// Beneficiaries designated by the owner and the inactivity period after
+/// which they can claim.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BeneficiaryPlan {
+    pub beneficiaries: Vec<Beneficiary>,
+    /// Time without owner-authorized calls before the release, in seconds
+    pub inactivity_period: u64,
+}
+
+/// Spendable balance being released to beneficiaries, fixed at the first
+/// claim so that every beneficiary's portion is computed against the same
+/// amount.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InheritanceRelease {
+    /// Owner activity timestamp the release was started after
+    pub last_owner_activity: u64,
+    pub balance: i128,
+    pub claimed: Vec<Address>,
+}
+
+/// Opt-in policy routing part of the wallet's yield to a community address.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct YieldDonationPolicy {
+    /// Donation campaign or community pool receiving the yield
+    pub recipient: Address,
+    /// Share of the harvested yield donated, in basis points
+    pub percentage_bps: u32,
+    /// Minimum time between two harvests, in seconds
+    pub interval: u64,
+}
+
+/// Opt-in policy rounding payments up and donating the difference.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoundUpPolicy {
+    /// Donation campaign or community pool receiving the round-ups
+    pub recipient: Address,
+    /// Payments are rounded up to the next multiple of this amount
+    pub increment: i128,
+    /// Maximum donated through round-ups per calendar month
+    pub monthly_cap: i128,
+}
+
+/// Preview of a payment, computed without changing any state.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentQuote {
+    /// Whether the destination is an approved provider
+    pub provider_approved: bool,
+    /// Whether the wallet's blocklist and allowlist mode permit the destination
+    pub allowed_by_wallet: bool,
+    /// Whether payments are paused by the protocol guardian
+    pub payments_paused: bool,
+    /// Whether the amount is above the approval policy's threshold, so the
+    /// payment must go through co-signer approval instead of `pay`
+    pub approval_required: bool,
+    /// Whether the wallet is frozen
+    pub frozen: bool,
+    /// Whether the wallet is closed
+    pub closed: bool,
+    /// Amount received by the provider
+    pub amount: i128,
+    /// Platform fee paid to the treasury on top of `amount`
+    pub fee: i128,
+    /// Round-up donated on top of `amount` by single payments, if the
+    /// balance covers it; batches are not rounded up
+    pub round_up: i128,
+    /// Vault shares burned to cover the amount and the fee
+    pub shares_to_burn: i128,
+    /// USDC the vault is expected to return for `shares_to_burn`
+    pub expected_output: i128,
+    /// Wallet balance before the payment
+    pub balance: i128,
+    /// Spendable balance left after the payment, negative if insufficient
+    pub remaining_balance: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VaultStatus {
+    Healthy,
+    /// At least one vault strategy is paused; withdrawals are limited to idle
+    /// funds and the active strategies
+    StrategiesPaused,
+    Unavailable,
+}
+
+/// Overview of the wallet returned by `wallet_summary`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WalletSummary {
+    pub owner: Address,
+    pub protocol_config: Address,
+    pub registry: Address,
+    pub usdc_token: Address,
+    pub defindex_vault: Address,
+    pub config_version: u32,
+    /// Whether the addresses were set by the owner rather than the protocol config
+    pub address_override: bool,
+    pub vault_status: VaultStatus,
+    pub balance: i128,
+    pub shares: i128,
+    pub principal: i128,
+    pub yield_earned: i128,
+    pub deposits_paused: bool,
+    pub payments_paused: bool,
+    pub idle_only: bool,
+    pub frozen: bool,
+    pub closed: bool,
+    /// Balance reserved by active holds
+    pub reserved: i128,
 }
 
 #[contracttype]
@@ -57,32 +419,214 @@ pub enum HealthAidWalletError {
     FailedToDeposit = 1004,
     FailedToGetBalance = 1005,
     FailedToWithdraw = 1006,
+    SnapshotTooSoon = 1007,
+    InvalidPercentage = 1008,
+    YieldDonationDisabled = 1009,
+    HarvestTooSoon = 1010,
+    ProtocolPaused = 1011,
+    EmptyBatch = 1012,
+    MemoTooLong = 1013,
+    SlippageExceeded = 1014,
+    VaultRejectedDeposit = 1015,
+    FailedToReadVault = 1016,
+    AllStrategiesPaused = 1017,
+    InsufficientIdleFunds = 1018,
+    Unauthorized = 1019,
+    WalletFrozen = 1020,
+    UnfreezeNotRequested = 1021,
+    UnfreezeTooSoon = 1022,
+    WalletClosed = 1023,
+    InvalidMigrationTarget = 1024,
+    InvalidBeneficiaries = 1025,
+    OwnerStillActive = 1026,
+    NotBeneficiary = 1027,
+    AlreadyClaimed = 1028,
+    EscrowNotFound = 1029,
+    InvalidEscrowState = 1030,
+    DisputeWindowOpen = 1031,
+    DisputeWindowClosed = 1032,
+    EscrowsPending = 1033,
+    HoldNotFound = 1034,
+    InvalidHoldState = 1035,
+    HoldExpired = 1036,
+    ExceedsHold = 1037,
+    HoldsActive = 1038,
+    /// No key is registered for the provider. A signature that does not
+    /// match the registered key traps in the host with `Error(Crypto, InvalidInput)`
+    InvalidSignature = 1039,
+    RequestExpired = 1040,
+    NonceAlreadyUsed = 1041,
+    UnsupportedCurrency = 1042,
+    ApprovalRequired = 1043,
+    InvalidApprovalPolicy = 1044,
+    ProposalNotFound = 1045,
+    InvalidProposalState = 1046,
+    ProposalExpired = 1047,
+    NotApprover = 1048,
+    AlreadyApproved = 1049,
+    InsufficientApprovals = 1050,
+    ProviderBlocked = 1051,
+    ProviderNotFavourite = 1052,
+    NoPendingConfig = 1053,
+    ConfigTimelockNotElapsed = 1054,
+    PositionNotEmpty = 1055,
+    ContributionLimitExceeded = 1056,
+    InvalidInterval = 1057,
+    InvalidMigrationSource = 1058,
+    RequestForOtherWallet = 1059,
 }
 
 #[contractimpl]
 impl HealthAidWallet {
     /// Initialize the wallet contract
     ///
+    /// The provider registry, USDC token and DeFindex vault addresses are read
+    /// from the protocol config and cached in the wallet.
+    ///
     /// # Arguments
     /// * `user` - The address of the wallet owner
-    /// * `registry_address` - The address of the ProviderRegistry contract
-    /// * `usdc_token` - The address of the USDC token contract
-    /// * `defindex_contract` - The address of the DeFindex/Blend contract
-    pub fn __constructor(
-        e: &Env,
-        user: Address,
-        registry_address: Address,
-        usdc_token: Address,
-        defindex_contract: Address,
-    ) {
+    /// * `protocol_config` - The address of the shared ProtocolConfig contract
+    pub fn __constructor(e: &Env, user: Address, protocol_config: Address) {
         e.storage().instance().set(&DataKey::User, &user);
         e.storage()
             .instance()
-            .set(&DataKey::RegistryAddress, &registry_address);
-        e.storage().instance().set(&DataKey::UsdcToken, &usdc_token);
+            .set(&DataKey::ProtocolConfig, &protocol_config);
+        Self::sync_config(e);
+        Self::record_owner_activity(e);
+    }
+
+    /// Refresh the cached addresses if the protocol config changed
+    ///
+    /// Called by every state-changing entrypoint; anyone can call it to bring
+    /// the getters below up to date.
+    ///
+    /// A new vault or USDC token is only adopted once the wallet's position
+    /// in the current ones is empty; until then only the registry is updated
+    /// and the version stays unchanged, so the update is retried.
+    ///
+    /// # Returns
+    /// * `u32` - The protocol config version now cached by the wallet
+    pub fn sync_config(e: &Env) -> u32 {
+        let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+        let version = config.get_version();
+        let cached_version = Self::get_config_version(e);
+        if cached_version == version {
+            return version;
+        }
+
+        // Addresses set by the owner with `execute_config` take precedence
+        if !Self::has_address_override(e) {
+            let addresses = config.get_addresses();
+            // Nothing is cached yet on the constructor's first sync
+            if cached_version > 0 && !Self::is_position_movable(e, &addresses) {
+                if Self::get_registry_address(e) != addresses.registry {
+                    e.storage()
+                        .instance()
+                        .set(&DataKey::RegistryAddress, &addresses.registry);
+                    Self::publish_audited(e, (symbol_short!("cfg_reg"),), addresses.registry);
+                }
+                return cached_version;
+            }
+            Self::set_addresses(e, &addresses);
+        }
+        e.storage()
+            .instance()
+            .set(&DataKey::ConfigVersion, &version);
+        Self::publish_audited(e, (symbol_short!("cfg_sync"),), version);
+        version
+    }
+
+    /// Queue a wallet-specific registry, USDC and DeFindex configuration
+    ///
+    /// The change can be executed after `UPDATE_TIMELOCK`, giving the owner
+    /// time to notice and cancel a change queued by a compromised front-end.
+    /// Once executed, the addresses no longer follow the protocol config.
+    ///
+    /// # Arguments
+    /// * `addresses` - The registry, USDC token and DeFindex vault to use
+    pub fn propose_config(e: &Env, addresses: ProtocolAddresses) {
+        Self::require_owner(e);
+
+        let pending = PendingAddressUpdate {
+            addresses,
+            executable_at: e.ledger().timestamp() + UPDATE_TIMELOCK,
+        };
+        e.storage()
+            .instance()
+            .set(&DataKey::PendingConfig, &pending);
+        Self::publish_audited(e, (symbol_short!("cfg_prop"),), pending);
+    }
+
+    /// Cancel the queued configuration change
+    pub fn cancel_config(e: &Env) {
+        Self::require_owner(e);
+
+        if Self::pending_config(e).is_none() {
+            panic_with_error!(e, HealthAidWalletError::NoPendingConfig);
+        }
+        e.storage().instance().remove(&DataKey::PendingConfig);
+        Self::publish_audited(e, (symbol_short!("cfg_cncl"),), ());
+    }
+
+    /// Get the queued configuration change, if any
+    pub fn pending_config(e: &Env) -> Option<PendingAddressUpdate> {
+        e.storage().instance().get(&DataKey::PendingConfig)
+    }
+
+    /// Apply the queued configuration change once its timelock has elapsed
+    ///
+    /// Switching the USDC token or the vault requires an empty vault position.
+    pub fn execute_config(e: &Env) {
+        Self::require_owner(e);
+
+        let pending = Self::pending_config(e).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::NoPendingConfig);
+        });
+        if e.ledger().timestamp() < pending.executable_at {
+            panic_with_error!(e, HealthAidWalletError::ConfigTimelockNotElapsed);
+        }
+        Self::require_position_movable(e, &pending.addresses);
+
+        e.storage().instance().remove(&DataKey::PendingConfig);
+        e.storage().instance().set(&DataKey::AddressOverride, &true);
+        Self::set_addresses(e, &pending.addresses);
+        Self::publish_audited(e, (symbol_short!("cfg_exec"),), pending.addresses);
+    }
+
+    /// Drop the wallet-specific configuration and follow the protocol config again
+    ///
+    /// The protocol config's own address changes are timelocked, so this
+    /// takes effect immediately.
+    pub fn follow_protocol_config(e: &Env) {
+        Self::require_owner(e);
+
+        let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+        let addresses = config.get_addresses();
+        Self::require_position_movable(e, &addresses);
+
+        e.storage().instance().remove(&DataKey::AddressOverride);
+        Self::set_addresses(e, &addresses);
+        e.storage()
+            .instance()
+            .set(&DataKey::ConfigVersion, &config.get_version());
+        Self::publish_audited(e, (symbol_short!("cfg_proto"),), addresses);
+    }
+
+    /// Whether the wallet uses addresses set by the owner instead of the
+    /// protocol config's
+    pub fn has_address_override(e: &Env) -> bool {
+        e.storage()
+            .instance()
+            .get(&DataKey::AddressOverride)
+            .unwrap_or(false)
+    }
+
+    /// Get the protocol config version the cached addresses were read at
+    pub fn get_config_version(e: &Env) -> u32 {
         e.storage()
             .instance()
-            .set(&DataKey::DefindexContract, &defindex_contract);
+            .get(&DataKey::ConfigVersion)
+            .unwrap_or(0)
     }
 
     /// Get the wallet owner
@@ -117,191 +661,2319 @@ impl HealthAidWallet {
             .expect("DeFindex contract address should be set")
     }
 
+    /// Get the shared ProtocolConfig contract address
+    pub fn get_protocol_config(e: &Env) -> Address {
+        e.storage()
+            .instance()
+            .get(&DataKey::ProtocolConfig)
+            .expect("Protocol config address should be set")
+    }
+
     /// Get the balance on Defindex
     pub fn get_balance(e: &Env) -> i128 {
-        let defindex_contract = Self::get_defindex_contract(e);
-
-        let balance_args: Vec<Val> = vec![e, e.current_contract_address().into_val(e)];
+        Self::try_get_balance(e).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::FailedToGetBalance);
+        })
+    }
 
-        let shares = e
-            .try_invoke_contract::<i128, InvokeError>(
-                &defindex_contract,
-                &Symbol::new(&e, "balance"),
-                balance_args.into_val(e),
-            )
-            .unwrap_or_else(|_| {
-                panic_with_error!(e, HealthAidWalletError::FailedToGetBalance);
-            })
-            .unwrap();
+    /// Get the total supply of DeFindex vault shares
+    pub fn get_total_supply(e: &Env) -> i128 {
+        Self::try_read_vault(e, "read_total_supply", vec![e]).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::FailedToReadVault);
+        })
+    }
 
-        let balance = e
-            .try_invoke_contract::<i128, InvokeError>(
-                &defindex_contract,
-                &Symbol::new(&e, "get_asset_amounts_per_shares"),
-                vec![e, shares.into_val(e)],
-            )
-            .unwrap_or_else(|_| {
-                panic_with_error!(e, HealthAidWalletError::FailedToGetBalance);
-            })
-            .unwrap();
-        return balance;
+    /// Get the total funds managed by the DeFindex vault
+    pub fn fetch_total_managed_funds(e: &Env) -> i128 {
+        Self::get_asset_allocation(e).total_amount
     }
 
-    pub fn get_total_supply(e: &Env) -> i128 {
-        let defindex_contract = Self::get_defindex_contract(e);
+    /// Get the full allocation breakdown of the DeFindex vault, per asset
+    pub fn get_vault_allocations(e: &Env) -> Vec<CurrentAssetInvestmentAllocation> {
+        Self::try_read_vault(e, "fetch_total_managed_funds", vec![e]).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::FailedToReadVault);
+        })
+    }
 
-        e.try_invoke_contract::<i128, InvokeError>(
-            &defindex_contract,
-            &Symbol::new(&e, "read_total_supply"),
-            vec![e],
-        )
-        .unwrap_or_else(|_| {
-            panic_with_error!(e, HealthAidWalletError::FailedToWithdraw);
+    /// Get the vault allocation of the wallet's USDC, including idle funds and
+    /// the amount held by each strategy
+    pub fn get_asset_allocation(e: &Env) -> CurrentAssetInvestmentAllocation {
+        Self::try_asset_allocation(e).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::FailedToReadVault);
         })
-        .unwrap()
     }
 
-    pub fn fetch_total_managed_funds(e: &Env) -> i128 {
-        let defindex_contract = Self::get_defindex_contract(e);
+    /// Get an overview of the wallet for dashboards
+    ///
+    /// Never fails because of the vault: if it cannot be read, `vault_status`
+    /// is `Unavailable` and the balance and yield are reported as zero.
+    pub fn wallet_summary(e: &Env) -> WalletSummary {
+        let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+        let principal = Self::get_principal(e);
+        let balance = Self::try_get_balance(e);
+        let vault_status = match (&balance, Self::try_asset_allocation(e)) {
+            (Some(_), Some(allocation)) if Self::any_strategy_paused(&allocation) => {
+                VaultStatus::StrategiesPaused
+            }
+            (Some(_), Some(_)) => VaultStatus::Healthy,
+            _ => VaultStatus::Unavailable,
+        };
+        // In idle-only mode the balance is held by the wallet and stays readable
+        let (balance, yield_earned) = match balance {
+            Some(balance) => (balance, balance - principal),
+            None => (0, 0),
+        };
 
-        let result = e
-            .try_invoke_contract::<Vec<CurrentAssetInvestmentAllocation>, InvokeError>(
-                &defindex_contract,
-                &Symbol::new(&e, "fetch_total_managed_funds"),
-                vec![&e],
-            )
-            .unwrap_or_else(|_| {
-                panic_with_error!(e, HealthAidWalletError::FailedToWithdraw);
-            })
-            .unwrap();
+        WalletSummary {
+            owner: Self::get_user(e),
+            protocol_config: Self::get_protocol_config(e),
+            registry: Self::get_registry_address(e),
+            usdc_token: Self::get_usdc_token(e),
+            defindex_vault: Self::get_defindex_contract(e),
+            config_version: Self::get_config_version(e),
+            address_override: Self::has_address_override(e),
+            vault_status,
+            balance,
+            shares: Self::get_shares(e),
+            principal,
+            yield_earned,
+            deposits_paused: config.is_deposits_paused(),
+            payments_paused: config.is_payments_paused(),
+            idle_only: Self::is_idle_only(e),
+            frozen: Self::is_frozen(e),
+            closed: Self::is_closed(e),
+            reserved: Self::get_reserved_balance(e),
+        }
+    }
 
-        return result.get(0).unwrap().total_amount;
+    /// Whether the wallet keeps its funds as plain USDC instead of in the vault
+    pub fn is_idle_only(e: &Env) -> bool {
+        e.storage()
+            .instance()
+            .get(&DataKey::IdleOnly)
+            .unwrap_or(false)
     }
 
-    /// Deposit USDC into the wallet
+    /// Redeem all vault shares to USDC held by the wallet
+    ///
+    /// Meant for a compromised vault: the wallet is switched into idle-only
+    /// mode, where deposits stay in the wallet and payments are made from the
+    /// wallet's own USDC, until the owner calls `enable_vault`.
     ///
     /// # Arguments
+    /// * `caller` - The wallet owner or the protocol guardian
     ///
-    /// * `e` - The environment context.
-    /// * `amount` - The amount to be deposited.
-    pub fn deposit(e: &Env, amount: i128) {
-        let user = Self::get_user(e);
-        user.require_auth();
-
-        if amount <= 0 {
-            panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+    /// # Returns
+    /// * `i128` - The amount of USDC redeemed from the vault
+    pub fn emergency_withdraw_all(e: &Env, caller: Address) -> i128 {
+        caller.require_auth();
+        let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+        if caller == Self::get_user(e) {
+            Self::record_owner_activity(e);
+        } else if caller != config.get_guardian() {
+            panic_with_error!(e, HealthAidWalletError::Unauthorized);
         }
 
-        let defindex_contract = Self::get_defindex_contract(e);
+        let shares = Self::get_shares(e);
+        let amount = if shares > 0 {
+            Self::set_shares(e, 0);
+            Self::redeem_shares(e, shares, 0)
+        } else {
+            0
+        };
+        e.storage().instance().set(&DataKey::IdleOnly, &true);
 
-        let deposit_args: Vec<Val> = vec![
-            e,
-            amount.into_val(e),
-            0i128.into_val(e),
-            e.current_contract_address().into_val(e),
-            true.into_val(e),
-        ];
+        Self::publish_audited(e, (symbol_short!("emergency"), caller), (shares, amount));
+        amount
+    }
 
-        e.authorize_as_current_contract(vec![
-            &e,
-            InvokerContractAuthEntry::Contract(SubContractInvocation {
-                context: ContractContext {
-                    contract: defindex_contract.clone().into(),
-                    fn_name: Symbol::new(&e, "deposit"),
-                    args: deposit_args.into_val(e),
-                },
-                sub_invocations: vec![&e],
-            }),
-        ]);
+    /// Leave idle-only mode and move the USDC held by the wallet back into the vault
+    ///
+    /// # Arguments
+    /// * `min_shares` - The minimum amount of vault shares to mint
+    ///
+    /// # Returns
+    /// * `i128` - The amount of vault shares minted
+    pub fn enable_vault(e: &Env, min_shares: i128) -> i128 {
+        Self::require_owner(e);
+        Self::sync_config(e);
+        Self::require_open(e);
+        Self::require_deposits_active(e);
 
-        e.try_invoke_contract::<i128, InvokeError>(
-            &defindex_contract,
-            &Symbol::new(&e, "deposit"),
-            deposit_args.into_val(e),
-        )
-        .unwrap_or_else(|_| {
-            panic_with_error!(e, HealthAidWalletError::FailedToDeposit);
-        })
-        .unwrap();
+        e.storage().instance().set(&DataKey::IdleOnly, &false);
+
+        let amount = Self::held_usdc(e);
+        let shares = if amount > 0 {
+            Self::deposit_into_vault(e, amount, min_shares)
+        } else {
+            0
+        };
+
+        Self::publish_audited(e, (symbol_short!("vault_on"),), (amount, shares));
+        shares
     }
 
-    /// Make a payment to a healthcare provider
+    /// Whether the wallet is frozen
+    pub fn is_frozen(e: &Env) -> bool {
+        e.storage()
+            .instance()
+            .get(&DataKey::Frozen)
+            .unwrap_or(false)
+    }
+
+    /// Get the time from which the owner can unfreeze the wallet alone, if an
+    /// unfreeze was requested
+    pub fn get_unfreeze_available_at(e: &Env) -> Option<u64> {
+        e.storage()
+            .instance()
+            .get::<_, u64>(&DataKey::UnfreezeRequestedAt)
+            .map(|requested_at| requested_at + UNFREEZE_DELAY)
+    }
+
+    /// Freeze the wallet, e.g. after the owner lost a device
+    ///
+    /// Payments, yield harvests and changes to the donation recipient are
+    /// blocked until the wallet is unfrozen. Freezing again cancels a pending
+    /// unfreeze request.
     ///
     /// # Arguments
-    /// * `destination` - Address of the healthcare provider
-    /// * `amount` - Amount to pay
-    pub fn pay(e: &Env, destination: Address, amount: i128) {
-        let user = Self::get_user(e);
-        user.require_auth();
-
-        if amount <= 0 {
-            panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+    /// * `caller` - The wallet owner or the protocol guardian
+    pub fn freeze(e: &Env, caller: Address) {
+        caller.require_auth();
+        let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+        if caller == Self::get_user(e) {
+            Self::record_owner_activity(e);
+        } else if caller != config.get_guardian() {
+            panic_with_error!(e, HealthAidWalletError::Unauthorized);
         }
 
-        let current_balance = Self::get_balance(e);
-        if current_balance < amount {
-            panic_with_error!(e, HealthAidWalletError::InsufficientBalance);
-        }
+        e.storage().instance().set(&DataKey::Frozen, &true);
+        e.storage().instance().remove(&DataKey::UnfreezeRequestedAt);
 
-        // Check if destination is an approved provider
-        let registry_address = Self::get_registry_address(e);
-        let registry_client = ProviderRegistryClient::new(e, &registry_address);
+        Self::publish_audited(e, (symbol_short!("frozen"), caller), ());
+    }
 
-        if !registry_client.is_provider(&destination) {
-            panic_with_error!(e, HealthAidWalletError::DestinationNotAllowed);
+    /// Start the unfreeze delay, after which the owner can unfreeze alone
+    pub fn request_unfreeze(e: &Env) {
+        Self::require_owner(e);
+        if !Self::is_frozen(e) {
+            return;
         }
 
-        //withdraw from defindex
-        let defindex_contract = Self::get_defindex_contract(e);
+        let now = e.ledger().timestamp();
+        e.storage()
+            .instance()
+            .set(&DataKey::UnfreezeRequestedAt, &now);
 
-        let total_supply = Self::get_total_supply(e);
-        let total_amount = Self::fetch_total_managed_funds(e);
+        Self::publish_audited(e, (symbol_short!("unfrz_req"),), now + UNFREEZE_DELAY);
+    }
 
-        let shares_needed = (amount.clone() * total_supply) / total_amount;
+    /// Unfreeze the wallet
+    ///
+    /// The owner can unfreeze immediately with the protocol guardian as
+    /// co-signer, or alone once `UNFREEZE_DELAY` has passed since
+    /// `request_unfreeze`.
+    ///
+    /// # Arguments
+    /// * `co_signer` - The protocol guardian, to skip the delay
+    pub fn unfreeze(e: &Env, co_signer: Option<Address>) {
+        Self::require_owner(e);
 
-        let withdraw_args: Vec<Val> = vec![
-            // now call withdraw with correct args
-            e,
-            shares_needed.into_val(e),
-            vec![&e, amount.clone()].into_val(e),
-            e.current_contract_address().into_val(e),
-        ];
+        match co_signer {
+            Some(co_signer) => {
+                let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+                if co_signer != config.get_guardian() {
+                    panic_with_error!(e, HealthAidWalletError::Unauthorized);
+                }
+                co_signer.require_auth();
+            }
+            None => {
+                let available_at = Self::get_unfreeze_available_at(e).unwrap_or_else(|| {
+                    panic_with_error!(e, HealthAidWalletError::UnfreezeNotRequested);
+                });
+                if e.ledger().timestamp() < available_at {
+                    panic_with_error!(e, HealthAidWalletError::UnfreezeTooSoon);
+                }
+            }
+        }
 
-        // Add authorization for DeFindex withdraw call
-        e.authorize_as_current_contract(vec![
-            &e,
-            InvokerContractAuthEntry::Contract(SubContractInvocation {
-                context: ContractContext {
-                    contract: defindex_contract.clone().into(),
-                    fn_name: Symbol::new(&e, "withdraw"),
-                    args: withdraw_args.into_val(e),
-                },
-                sub_invocations: vec![&e],
-            }),
-        ]);
+        e.storage().instance().remove(&DataKey::Frozen);
+        e.storage().instance().remove(&DataKey::UnfreezeRequestedAt);
 
-        e.try_invoke_contract::<i128, InvokeError>(
-            &defindex_contract,
-            &Symbol::new(&e, "withdraw"),
+        Self::publish_audited(e, (symbol_short!("unfrozen"),), ());
+    }
+
+    /// Designate beneficiaries who can claim the wallet after the owner has
+    /// been inactive for `inactivity_period`
+    ///
+    /// Inactivity is measured from the last owner-authorized call, so any owner
+    /// action cancels a release in progress.
+    ///
+    /// # Arguments
+    /// * `beneficiaries` - Beneficiaries and their shares, summing to 100%
+    /// * `inactivity_period` - At least `MIN_INACTIVITY_PERIOD`, in seconds
+    pub fn set_beneficiaries(e: &Env, beneficiaries: Vec<Beneficiary>, inactivity_period: u64) {
+        Self::require_owner(e);
+
+        if beneficiaries.is_empty() || inactivity_period < MIN_INACTIVITY_PERIOD {
+            panic_with_error!(e, HealthAidWalletError::InvalidBeneficiaries);
+        }
+        let mut total_bps: u32 = 0;
+        for (i, beneficiary) in beneficiaries.iter().enumerate() {
+            let duplicate = beneficiaries
+                .iter()
+                .skip(i + 1)
+                .any(|other| other.address == beneficiary.address);
+            if beneficiary.share_bps == 0 || duplicate {
+                panic_with_error!(e, HealthAidWalletError::InvalidBeneficiaries);
+            }
+            total_bps = total_bps.saturating_add(beneficiary.share_bps);
+        }
+        if total_bps as i128 != BPS_DENOMINATOR {
+            panic_with_error!(e, HealthAidWalletError::InvalidBeneficiaries);
+        }
+
+        let plan = BeneficiaryPlan {
+            beneficiaries,
+            inactivity_period,
+        };
+        e.storage().instance().set(&DataKey::BeneficiaryPlan, &plan);
+        Self::publish_audited(e, (symbol_short!("benef_set"),), plan);
+    }
+
+    /// Remove the beneficiary designation
+    pub fn remove_beneficiaries(e: &Env) {
+        Self::require_owner(e);
+
+        e.storage().instance().remove(&DataKey::BeneficiaryPlan);
+        e.storage().instance().remove(&DataKey::InheritanceRelease);
+        Self::publish_audited(e, (symbol_short!("benef_del"),), ());
+    }
+
+    /// Get the beneficiary plan, if the owner designated beneficiaries
+    pub fn get_beneficiaries(e: &Env) -> Option<BeneficiaryPlan> {
+        e.storage().instance().get(&DataKey::BeneficiaryPlan)
+    }
+
+    /// Get the time of the last owner-authorized call
+    pub fn get_last_owner_activity(e: &Env) -> u64 {
+        e.storage()
+            .instance()
+            .get(&DataKey::LastOwnerActivity)
+            .unwrap_or(0)
+    }
+
+    /// Get the time from which beneficiaries can claim, if any are designated
+    pub fn get_release_available_at(e: &Env) -> Option<u64> {
+        Self::get_beneficiaries(e)
+            .map(|plan| Self::get_last_owner_activity(e) + plan.inactivity_period)
+    }
+
+    /// Claim a beneficiary's portion after the owner's inactivity period
+    ///
+    /// The portion is the beneficiary's share of the spendable balance at the
+    /// first claim of the release. Funds reserved by active holds stay in the
+    /// wallet for the providers to capture.
+    ///
+    /// # Arguments
+    /// * `beneficiary` - The claiming beneficiary
+    ///
+    /// # Returns
+    /// * `i128` - The amount of USDC transferred to the beneficiary
+    pub fn claim_inheritance(e: &Env, beneficiary: Address) -> i128 {
+        beneficiary.require_auth();
+        Self::require_open(e);
+
+        let plan = Self::get_beneficiaries(e).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::NotBeneficiary);
+        });
+        let share_bps = plan
+            .beneficiaries
+            .iter()
+            .find(|b| b.address == beneficiary)
+            .map(|b| b.share_bps)
+            .unwrap_or_else(|| panic_with_error!(e, HealthAidWalletError::NotBeneficiary));
+
+        let last_owner_activity = Self::get_last_owner_activity(e);
+        if e.ledger().timestamp() < last_owner_activity + plan.inactivity_period {
+            panic_with_error!(e, HealthAidWalletError::OwnerStillActive);
+        }
+        Self::sync_config(e);
+
+        // A release started before the owner's last activity was cancelled
+        let current_balance = Self::get_balance(e);
+        let spendable = current_balance - Self::get_reserved_balance(e);
+        let mut release = match e
+            .storage()
+            .instance()
+            .get::<_, InheritanceRelease>(&DataKey::InheritanceRelease)
+        {
+            Some(release) if release.last_owner_activity == last_owner_activity => release,
+            _ => InheritanceRelease {
+                last_owner_activity,
+                balance: spendable,
+                claimed: Vec::new(e),
+            },
+        };
+        if release.claimed.contains(&beneficiary) {
+            panic_with_error!(e, HealthAidWalletError::AlreadyClaimed);
+        }
+        release.claimed.push_back(beneficiary.clone());
+        e.storage()
+            .instance()
+            .set(&DataKey::InheritanceRelease, &release);
+
+        let amount = (release.balance * share_bps as i128 / BPS_DENOMINATOR).min(spendable);
+        if amount > 0 {
+            Self::withdraw_from_vault(e, amount);
+            Self::transfer_usdc(e, &beneficiary, amount);
+            Self::reduce_principal(e, amount, current_balance);
+            Self::record_transaction(e, TransactionKind::Withdrawal, &beneficiary, amount, None);
+        }
+        amount
+    }
+
+    /// Whether the wallet was closed or migrated
+    pub fn is_closed(e: &Env) -> bool {
+        e.storage()
+            .instance()
+            .get(&DataKey::Closed)
+            .unwrap_or(false)
+    }
+
+    /// Move the whole position into another HealthAidWallet and close this one
+    ///
+    /// The vault position is redeemed and the USDC transferred to `new_wallet`,
+    /// which deposits it with `receive_migration`. The target must be a wallet
+    /// registered in the protocol config with the same owner and protocol
+    /// config as this wallet, and escrowed payments must be settled first.
+    /// With an approval policy set, `required_approvals` of its signers must
+    /// co-sign.
+    ///
+    /// # Arguments
+    /// * `new_wallet` - The HealthAidWallet receiving the funds
+    /// * `co_signers` - Policy signers approving the migration
+    ///
+    /// # Returns
+    /// * `i128` - The amount of USDC transferred
+    pub fn migrate_to(e: &Env, new_wallet: Address, co_signers: Vec<Address>) -> i128 {
+        Self::require_owner(e);
+        if let Some(policy) = Self::get_approval_policy(e) {
+            Self::require_co_signers(e, &policy, &co_signers);
+        }
+
+        if new_wallet == e.current_contract_address() || !Self::is_sibling_wallet(e, &new_wallet) {
+            panic_with_error!(e, HealthAidWalletError::InvalidMigrationTarget);
+        }
+
+        let principal = Self::get_principal(e);
+        let amount = Self::close_out(e, &new_wallet);
+        HealthAidWalletClient::new(e, &new_wallet).receive_migration(
+            &e.current_contract_address(),
+            &amount,
+            &principal,
+        );
+        Self::publish_audited(e, (symbol_short!("migrated"), new_wallet), amount);
+        amount
+    }
+
+    /// Deposit the funds migrated from another wallet of the same owner
+    ///
+    /// Called by the old wallet from `migrate_to`, after transferring the
+    /// USDC. Only registered wallets are accepted; they run the platform's
+    /// code, which checks the target's owner before migrating. The principal
+    /// is carried over and the funds do not count towards the yearly
+    /// contribution caps.
+    ///
+    /// # Arguments
+    /// * `from` - The wallet the funds were migrated from
+    /// * `amount` - The amount of USDC transferred
+    /// * `principal` - The principal of the old wallet
+    pub fn receive_migration(e: &Env, from: Address, amount: i128, principal: i128) {
+        from.require_auth();
+
+        Self::sync_config(e);
+        Self::require_open(e);
+        if !Self::is_registered_wallet(e, &from) {
+            panic_with_error!(e, HealthAidWalletError::InvalidMigrationSource);
+        }
+
+        if amount > 0 {
+            Self::deposit_into_vault(e, amount, 0);
+        }
+        Self::add_principal(e, principal);
+        Self::record_transaction(e, TransactionKind::Deposit, &from, amount, None);
+    }
+
+    /// Return all funds to the owner and close the wallet
+    ///
+    /// A closed wallet no longer accepts deposits. Escrowed payments must be
+    /// settled first. With an approval policy set, `required_approvals` of its
+    /// signers must co-sign.
+    ///
+    /// # Arguments
+    /// * `co_signers` - Policy signers approving the closure
+    ///
+    /// # Returns
+    /// * `i128` - The amount of USDC returned to the owner
+    pub fn close(e: &Env, co_signers: Vec<Address>) -> i128 {
+        let user = Self::require_owner(e);
+        if let Some(policy) = Self::get_approval_policy(e) {
+            Self::require_co_signers(e, &policy, &co_signers);
+        }
+
+        let amount = Self::close_out(e, &user);
+        Self::publish_audited(e, (symbol_short!("closed"),), amount);
+        amount
+    }
+
+    /// Deposit USDC into the wallet
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - The environment context.
+    /// * `amount` - The amount to be deposited.
+    /// * `min_shares` - The minimum amount of vault shares to mint.
+    ///
+    /// # Returns
+    ///
+    /// * `i128` - The amount of vault shares minted.
+    pub fn deposit(e: &Env, amount: i128, min_shares: i128) -> i128 {
+        let user = Self::require_owner(e);
+
+        if amount <= 0 || min_shares < 0 {
+            panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+        }
+        Self::sync_config(e);
+        Self::require_open(e);
+        Self::require_deposits_active(e);
+        // Escrowed USDC is owed to providers and cannot be deposited again
+        if amount > Self::held_usdc(e) {
+            panic_with_error!(e, HealthAidWalletError::InsufficientBalance);
+        }
+        Self::record_contribution(e, &user, amount);
+
+        let shares = Self::deposit_into_vault(e, amount, min_shares);
+        Self::add_principal(e, amount);
+        Self::record_transaction(e, TransactionKind::Deposit, &user, amount, None);
+        shares
+    }
+
+    /// Get the deposits made in a calendar year
+    ///
+    /// # Arguments
+    /// * `year` - The calendar year, in UTC
+    pub fn contributions_for_year(e: &Env, year: u32) -> YearContributions {
+        e.storage()
+            .persistent()
+            .get(&DataKey::Contributions(year))
+            .unwrap_or_default()
+    }
+
+    /// Deposit USDC into the wallet on behalf of a third party
+    ///
+    /// The funder (a donor, employer or family member) transfers USDC into the
+    /// wallet, which is then pushed into the DeFindex vault in the same call.
+    ///
+    /// # Arguments
+    /// * `funder` - The address funding the wallet, must authorize the transfer
+    /// * `amount` - The amount of USDC to deposit
+    /// * `min_shares` - The minimum amount of vault shares to mint
+    ///
+    /// # Returns
+    /// * `i128` - The amount of vault shares minted
+    pub fn deposit_from(e: &Env, funder: Address, amount: i128, min_shares: i128) -> i128 {
+        funder.require_auth();
+
+        if amount <= 0 || min_shares < 0 {
+            panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+        }
+        Self::sync_config(e);
+        Self::require_open(e);
+        Self::require_deposits_active(e);
+        Self::record_contribution(e, &funder, amount);
+
+        let usdc_token = Self::get_usdc_token(e);
+        token::Client::new(e, &usdc_token).transfer(
+            &funder,
+            &e.current_contract_address(),
+            &amount,
+        );
+
+        let shares = Self::deposit_into_vault(e, amount, min_shares);
+        Self::add_principal(e, amount);
+        Self::record_transaction(e, TransactionKind::Deposit, &funder, amount, None);
+        shares
+    }
+
+    /// Get the number of entries in the transaction history
+    pub fn get_history_count(e: &Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&DataKey::HistoryCount)
+            .unwrap_or(0)
+    }
+
+    /// Get a page of the transaction history, oldest first
+    ///
+    /// # Arguments
+    /// * `start` - Index of the first entry to return
+    /// * `limit` - Maximum number of entries to return
+    pub fn get_history(e: &Env, start: u32, limit: u32) -> Vec<TransactionRecord> {
+        let count = Self::get_history_count(e);
+        let end = start.saturating_add(limit).min(count);
+
+        let mut records = Vec::new(e);
+        for index in start..end {
+            if let Some(record) = e.storage().persistent().get(&DataKey::History(index)) {
+                records.push_back(record);
+            }
+        }
+        records
+    }
+
+    /// Get the vault shares minted to the wallet, net of shares redeemed
+    pub fn get_shares(e: &Env) -> i128 {
+        e.storage().instance().get(&DataKey::Shares).unwrap_or(0)
+    }
+
+    /// Get the net principal contributed to the wallet
+    ///
+    /// Deposits add to the principal at face value; payments reduce it at cost
+    /// basis, i.e. proportionally to the share of the vault position withdrawn.
+    pub fn get_principal(e: &Env) -> i128 {
+        e.storage().instance().get(&DataKey::Principal).unwrap_or(0)
+    }
+
+    /// Get the memo attached to a history entry, if any
+    ///
+    /// # Arguments
+    /// * `index` - Index of the entry in the transaction history
+    pub fn get_payment_memo(e: &Env, index: u32) -> Option<PaymentMemo> {
+        e.storage().persistent().get(&DataKey::PaymentMemo(index))
+    }
+
+    /// Get the yield earned on top of the principal
+    ///
+    /// # Returns
+    /// * `i128` - Current vault balance minus principal, negative if the vault
+    ///   position is worth less than what was contributed
+    pub fn get_yield_earned(e: &Env) -> i128 {
+        Self::get_balance(e) - Self::get_principal(e)
+    }
+
+    /// Record a snapshot of the balance, principal and yield earned
+    ///
+    /// Anyone can call this, at most once every `YIELD_SNAPSHOT_INTERVAL`.
+    pub fn take_yield_snapshot(e: &Env) -> YieldSnapshot {
+        Self::sync_config(e);
+
+        let count = Self::get_yield_snapshot_count(e);
+        let now = e.ledger().timestamp();
+
+        if count > 0 {
+            let last: YieldSnapshot = e
+                .storage()
+                .persistent()
+                .get(&DataKey::YieldSnapshot(count - 1))
+                .expect("Yield snapshot should be set");
+            if now < last.timestamp + YIELD_SNAPSHOT_INTERVAL {
+                panic_with_error!(e, HealthAidWalletError::SnapshotTooSoon);
+            }
+        }
+
+        let balance = Self::get_balance(e);
+        let principal = Self::get_principal(e);
+        let snapshot = YieldSnapshot {
+            timestamp: now,
+            balance,
+            principal,
+            yield_earned: balance - principal,
+        };
+
+        e.storage()
+            .persistent()
+            .set(&DataKey::YieldSnapshot(count), &snapshot);
+        e.storage()
+            .instance()
+            .set(&DataKey::YieldSnapshotCount, &(count + 1));
+
+        snapshot
+    }
+
+    /// Get the number of recorded yield snapshots
+    pub fn get_yield_snapshot_count(e: &Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&DataKey::YieldSnapshotCount)
+            .unwrap_or(0)
+    }
+
+    /// Get a page of the yield snapshot history, oldest first
+    ///
+    /// # Arguments
+    /// * `start` - Index of the first snapshot to return
+    /// * `limit` - Maximum number of snapshots to return
+    pub fn get_yield_snapshots(e: &Env, start: u32, limit: u32) -> Vec<YieldSnapshot> {
+        let count = Self::get_yield_snapshot_count(e);
+        let end = start.saturating_add(limit).min(count);
+
+        let mut snapshots = Vec::new(e);
+        for index in start..end {
+            if let Some(snapshot) = e.storage().persistent().get(&DataKey::YieldSnapshot(index)) {
+                snapshots.push_back(snapshot);
+            }
+        }
+        snapshots
+    }
+
+    /// Opt in to donating part of the wallet's yield
+    ///
+    /// # Arguments
+    /// * `recipient` - Donation campaign or community pool receiving the yield
+    /// * `percentage_bps` - Share of the yield to donate, in basis points
+    /// * `interval` - Minimum time between two harvests, in seconds
+    pub fn set_yield_donation(e: &Env, recipient: Address, percentage_bps: u32, interval: u64) {
+        Self::require_owner(e);
+        Self::require_not_frozen(e);
+
+        if percentage_bps == 0 || percentage_bps as i128 > BPS_DENOMINATOR {
+            panic_with_error!(e, HealthAidWalletError::InvalidPercentage);
+        }
+        if interval == 0 {
+            panic_with_error!(e, HealthAidWalletError::InvalidInterval);
+        }
+
+        let policy = YieldDonationPolicy {
+            recipient,
+            percentage_bps,
+            interval,
+        };
+        e.storage()
+            .instance()
+            .set(&DataKey::YieldDonationPolicy, &policy);
+        Self::publish_audited(e, (symbol_short!("yield_don"),), policy);
+    }
+
+    /// Opt out of yield donations
+    pub fn disable_yield_donation(e: &Env) {
+        Self::require_owner(e);
+
+        e.storage().instance().remove(&DataKey::YieldDonationPolicy);
+        Self::publish_audited(e, (symbol_short!("yield_don"),), ());
+    }
+
+    /// Get the yield donation policy, if the owner opted in
+    pub fn get_yield_donation(e: &Env) -> Option<YieldDonationPolicy> {
+        e.storage().instance().get(&DataKey::YieldDonationPolicy)
+    }
+
+    /// Harvest the yield above principal and donate the configured share of it
+    ///
+    /// Anyone can call this, at most once per policy interval. The yield is
+    /// valued at the current DeFindex share price; the principal is untouched.
+    /// Only the yield accrued since the previous harvest is donated from.
+    ///
+    /// # Returns
+    /// * `i128` - The amount donated
+    pub fn harvest_yield(e: &Env) -> i128 {
+        let policy = Self::get_yield_donation(e).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::YieldDonationDisabled);
+        });
+        Self::sync_config(e);
+        Self::require_payments_active(e);
+        Self::require_not_frozen(e);
+
+        let now = e.ledger().timestamp();
+        if let Some(last_harvest) = e.storage().instance().get::<_, u64>(&DataKey::LastHarvest) {
+            if now < last_harvest + policy.interval {
+                panic_with_error!(e, HealthAidWalletError::HarvestTooSoon);
+            }
+        }
+        e.storage().instance().set(&DataKey::LastHarvest, &now);
+
+        // Only yield accrued since the last harvest is donated; the share the
+        // owner kept is not taxed again
+        let yield_earned = Self::get_yield_earned(e);
+        let new_yield = yield_earned - Self::get_harvested_yield(e);
+        let donation = (new_yield.max(0) * policy.percentage_bps as i128 / BPS_DENOMINATOR)
+            .min(Self::get_spendable_balance(e).max(0));
+        if new_yield > 0 {
+            e.storage()
+                .instance()
+                .set(&DataKey::HarvestedYield, &(yield_earned - donation));
+        }
+        if donation == 0 {
+            return 0;
+        }
+
+        Self::withdraw_from_vault(e, donation);
+        Self::donate(e, &policy.recipient, donation);
+
+        donation
+    }
+
+    /// Opt in to rounding payments up and donating the difference
+    ///
+    /// Applies to single payments: `pay`, `pay_with_memo` and `pay_request`.
+    /// Batches, escrows, holds and co-signed proposals are not rounded up. The
+    /// round-up never exceeds the payment and counts towards the approval
+    /// threshold; it is skipped when the spendable balance does not cover it,
+    /// so it never blocks a payment.
+    ///
+    /// # Arguments
+    /// * `recipient` - Donation campaign or community pool receiving the round-ups
+    /// * `increment` - Payments are rounded up to the next multiple of this
+    ///   amount, at most `MAX_ROUND_UP_INCREMENT`
+    /// * `monthly_cap` - Maximum donated through round-ups per calendar month
+    pub fn set_round_up(e: &Env, recipient: Address, increment: i128, monthly_cap: i128) {
+        Self::require_owner(e);
+        Self::require_not_frozen(e);
+
+        if increment <= 0 || increment > MAX_ROUND_UP_INCREMENT || monthly_cap < 0 {
+            panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+        }
+
+        let policy = RoundUpPolicy {
+            recipient,
+            increment,
+            monthly_cap,
+        };
+        e.storage().instance().set(&DataKey::RoundUpPolicy, &policy);
+        Self::publish_audited(e, (symbol_short!("round_up"),), policy);
+    }
+
+    /// Opt out of round-up donations
+    pub fn disable_round_up(e: &Env) {
+        Self::require_owner(e);
+
+        e.storage().instance().remove(&DataKey::RoundUpPolicy);
+        Self::publish_audited(e, (symbol_short!("round_up"),), ());
+    }
+
+    /// Get the round-up policy, if the owner opted in
+    pub fn get_round_up(e: &Env) -> Option<RoundUpPolicy> {
+        e.storage().instance().get(&DataKey::RoundUpPolicy)
+    }
+
+    /// Get the amount donated through round-ups in a calendar month
+    ///
+    /// # Arguments
+    /// * `year` - The calendar year, in UTC
+    /// * `month` - The month, from 1 to 12
+    pub fn get_round_up_donated(e: &Env, year: u32, month: u32) -> i128 {
+        e.storage()
+            .persistent()
+            .get(&DataKey::RoundUpDonated(year, month))
+            .unwrap_or(0)
+    }
+
+    /// Get the head of the wallet's audit chain
+    ///
+    /// Every event the wallet emits extends the chain: the new head is the
+    /// SHA-256 of the previous head followed by the XDR of the event's topics
+    /// and data. The chain starts from 32 zero bytes, so replaying the
+    /// wallet's events reproduces the head and shows no entry was altered.
+    pub fn get_audit_head(e: &Env) -> BytesN<32> {
+        e.storage()
+            .instance()
+            .get(&DataKey::AuditHead)
+            .unwrap_or_else(|| BytesN::from_array(e, &[0; 32]))
+    }
+
+    /// Get the total donated by the wallet, from round-ups and yield harvests
+    pub fn get_total_donated(e: &Env) -> i128 {
+        e.storage()
+            .instance()
+            .get(&DataKey::TotalDonated)
+            .unwrap_or(0)
+    }
+
+    /// Make a payment to a healthcare provider
+    ///
+    /// The platform fee configured in the protocol config, if any, is paid to
+    /// the treasury on top of `amount`, as is the round-up donation if the
+    /// owner opted in.
+    ///
+    /// # Arguments
+    /// * `destination` - Address of the healthcare provider
+    /// * `amount` - Amount to pay
+    pub fn pay(e: &Env, destination: Address, amount: i128) {
+        Self::require_owner(e);
+
+        Self::settle_rounded_up(e, destination, amount, None);
+    }
+
+    /// Make a payment to a healthcare provider with a reconciliation memo
+    ///
+    /// Fees and the round-up donation are paid as with `pay`.
+    ///
+    /// # Arguments
+    /// * `destination` - Address of the healthcare provider
+    /// * `amount` - Amount to pay
+    /// * `memo` - Invoice reference and hashed procedure and note
+    pub fn pay_with_memo(e: &Env, destination: Address, amount: i128, memo: PaymentMemo) {
+        Self::require_owner(e);
+
+        if memo.invoice_ref.len() > MAX_INVOICE_REF_LEN {
+            panic_with_error!(e, HealthAidWalletError::MemoTooLong);
+        }
+
+        Self::settle_rounded_up(e, destination, amount, Some(memo));
+    }
+
+    /// Preview a payment before signing it
+    ///
+    /// Read-only: uses the addresses cached at the last `sync_config`. Never
+    /// fails because of the vault: if it is empty or cannot be read, no shares
+    /// are quoted and the balance is reported as zero.
+    ///
+    /// # Arguments
+    /// * `destination` - Address of the healthcare provider
+    /// * `amount` - Amount to pay
+    pub fn quote_payment(e: &Env, destination: Address, amount: i128) -> PaymentQuote {
+        if amount <= 0 {
+            panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+        }
+
+        let registry_client = ProviderRegistryClient::new(e, &Self::get_registry_address(e));
+        let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+
+        let fee = match config.get_fee_config() {
+            Some(fee_config) => {
+                Self::payment_fee(&fee_config, &registry_client, &destination, amount)
+            }
+            None => 0,
+        };
+        let (shares_to_burn, expected_output) = if Self::is_idle_only(e) {
+            (0, amount + fee)
+        } else {
+            match Self::try_asset_allocation(e) {
+                Some(allocation) if allocation.total_amount > 0 => {
+                    let shares = Self::shares_for_amount(e, amount + fee, allocation.total_amount);
+                    (shares, Self::amount_for_shares(e, shares))
+                }
+                _ => (0, 0),
+            }
+        };
+        let balance = Self::try_get_balance(e).unwrap_or(0);
+        let round_up = Self::round_up_amount(e, amount);
+
+        PaymentQuote {
+            provider_approved: registry_client.is_provider(&destination),
+            allowed_by_wallet: Self::is_allowed_by_wallet(e, &destination),
+            payments_paused: config.is_payments_paused(),
+            approval_required: Self::exceeds_approval_threshold(e, amount + round_up),
+            frozen: Self::is_frozen(e),
+            closed: Self::is_closed(e),
+            amount,
+            fee,
+            round_up,
+            shares_to_burn,
+            expected_output,
+            balance,
+            remaining_balance: balance - Self::get_reserved_balance(e) - amount - fee,
+        }
+    }
+
+    /// Pay a provider-signed payment request
+    ///
+    /// Verifies the provider's ed25519 signature over the request and pays
+    /// exactly the requested amount, with the invoice reference as memo, plus
+    /// fees and the round-up donation as with `pay`. Each nonce can be paid
+    /// once per provider. A signature that does not match
+    /// the provider's registered key aborts the call with the host error
+    /// `Error(Crypto, InvalidInput)`.
+    ///
+    /// # Arguments
+    /// * `request` - The payment request
+    /// * `signature` - The provider's ed25519 signature over the request's XDR
+    pub fn pay_request(e: &Env, request: PaymentRequest, signature: BytesN<64>) {
+        Self::require_owner(e);
+
+        if request.invoice_ref.len() > MAX_INVOICE_REF_LEN {
+            panic_with_error!(e, HealthAidWalletError::MemoTooLong);
+        }
+        if request.wallet != e.current_contract_address() {
+            panic_with_error!(e, HealthAidWalletError::RequestForOtherWallet);
+        }
+        if e.ledger().timestamp() >= request.expiry {
+            panic_with_error!(e, HealthAidWalletError::RequestExpired);
+        }
+        Self::sync_config(e);
+        if request.currency != Self::get_usdc_token(e) {
+            panic_with_error!(e, HealthAidWalletError::UnsupportedCurrency);
+        }
+
+        let registry_client = ProviderRegistryClient::new(e, &Self::get_registry_address(e));
+        let key = registry_client
+            .get_provider_key(&request.provider)
+            .unwrap_or_else(|| panic_with_error!(e, HealthAidWalletError::InvalidSignature));
+        e.crypto()
+            .ed25519_verify(&key, &request.clone().to_xdr(e), &signature);
+
+        let nonce_key = DataKey::RequestNonce(request.provider.clone(), request.nonce);
+        if e.storage().persistent().has(&nonce_key) {
+            panic_with_error!(e, HealthAidWalletError::NonceAlreadyUsed);
+        }
+        e.storage().persistent().set(&nonce_key, &true);
+
+        let memo = PaymentMemo {
+            invoice_ref: request.invoice_ref,
+            procedure_hash: None,
+            note_hash: None,
+        };
+        Self::settle_rounded_up(e, request.provider, request.amount, Some(memo));
+    }
+
+    /// Check whether a provider's payment request nonce was already paid
+    pub fn is_request_paid(e: &Env, provider: Address, nonce: u64) -> bool {
+        e.storage()
+            .persistent()
+            .has(&DataKey::RequestNonce(provider, nonce))
+    }
+
+    /// Pay several healthcare providers atomically
+    ///
+    /// Every destination must be an approved provider. The aggregate amount is
+    /// withdrawn from the vault in a single DeFindex withdrawal. Batches are
+    /// not rounded up.
+    ///
+    /// # Arguments
+    /// * `payments` - Pairs of provider address and amount to pay
+    pub fn pay_batch(e: &Env, payments: Vec<(Address, i128)>) {
+        Self::require_owner(e);
+
+        if payments.is_empty() {
+            panic_with_error!(e, HealthAidWalletError::EmptyBatch);
+        }
+
+        Self::settle_payments(e, &payments, None, false);
+    }
+
+    /// Add a provider to the wallet's favourites
+    ///
+    /// In allowlist mode only favourite providers can be paid.
+    pub fn add_favourite(e: &Env, provider: Address) {
+        Self::require_owner(e);
+
+        e.storage()
+            .persistent()
+            .set(&DataKey::Favourite(provider.clone()), &true);
+        Self::publish_audited(e, (symbol_short!("fav_add"), provider), ());
+    }
+
+    /// Remove a provider from the wallet's favourites
+    pub fn remove_favourite(e: &Env, provider: Address) {
+        Self::require_owner(e);
+
+        e.storage()
+            .persistent()
+            .remove(&DataKey::Favourite(provider.clone()));
+        Self::publish_audited(e, (symbol_short!("fav_del"), provider), ());
+    }
+
+    /// Check if a provider is one of the wallet's favourites
+    pub fn is_favourite(e: &Env, provider: Address) -> bool {
+        e.storage()
+            .persistent()
+            .get(&DataKey::Favourite(provider))
+            .unwrap_or(false)
+    }
+
+    /// Restrict payments to favourite providers, or lift the restriction
+    pub fn set_allowlist_mode(e: &Env, enabled: bool) {
+        Self::require_owner(e);
+
+        e.storage()
+            .instance()
+            .set(&DataKey::AllowlistMode, &enabled);
+        Self::publish_audited(e, (symbol_short!("allowlist"),), enabled);
+    }
+
+    /// Whether payments are restricted to favourite providers
+    pub fn is_allowlist_mode(e: &Env) -> bool {
+        e.storage()
+            .instance()
+            .get(&DataKey::AllowlistMode)
+            .unwrap_or(false)
+    }
+
+    /// Block payments from this wallet to a provider
+    pub fn block_provider(e: &Env, provider: Address) {
+        Self::require_owner(e);
+
+        e.storage()
+            .persistent()
+            .set(&DataKey::Blocked(provider.clone()), &true);
+        Self::publish_audited(e, (symbol_short!("blocked"), provider), ());
+    }
+
+    /// Lift a block placed with `block_provider`
+    pub fn unblock_provider(e: &Env, provider: Address) {
+        Self::require_owner(e);
+
+        e.storage()
+            .persistent()
+            .remove(&DataKey::Blocked(provider.clone()));
+        Self::publish_audited(e, (symbol_short!("unblocked"), provider), ());
+    }
+
+    /// Check if the wallet blocked a provider
+    pub fn is_blocked(e: &Env, provider: Address) -> bool {
+        e.storage()
+            .persistent()
+            .get(&DataKey::Blocked(provider))
+            .unwrap_or(false)
+    }
+
+    /// Require co-signer approval for payments above `threshold_amount`
+    ///
+    /// Replacing or removing an existing policy must be co-signed by
+    /// `required_approvals` of its current signers.
+    ///
+    /// # Arguments
+    /// * `policy` - The new approval policy, or `None` to remove it
+    /// * `co_signers` - Current signers approving the change
+    pub fn set_approval_policy(e: &Env, policy: Option<ApprovalPolicy>, co_signers: Vec<Address>) {
+        Self::require_owner(e);
+
+        if let Some(current) = Self::get_approval_policy(e) {
+            Self::require_co_signers(e, &current, &co_signers);
+        }
+
+        match policy {
+            Some(policy) => {
+                if policy.threshold_amount < 0
+                    || policy.required_approvals == 0
+                    || policy.required_approvals > policy.signers.len()
+                    || policy.proposal_ttl == 0
+                    || policy.signers.contains(Self::get_user(e))
+                    || Self::has_duplicates(&policy.signers)
+                {
+                    panic_with_error!(e, HealthAidWalletError::InvalidApprovalPolicy);
+                }
+                e.storage()
+                    .instance()
+                    .set(&DataKey::ApprovalPolicy, &policy);
+                Self::publish_audited(e, (symbol_short!("policy"),), policy);
+            }
+            None => {
+                e.storage().instance().remove(&DataKey::ApprovalPolicy);
+                Self::publish_audited(e, (symbol_short!("policy"),), ());
+            }
+        }
+    }
+
+    /// Get the co-signer policy for large payments, if one is set
+    pub fn get_approval_policy(e: &Env) -> Option<ApprovalPolicy> {
+        e.storage().instance().get(&DataKey::ApprovalPolicy)
+    }
+
+    /// Propose a payment above the approval threshold
+    ///
+    /// # Arguments
+    /// * `destination` - Healthcare provider address
+    /// * `amount` - Amount to pay
+    ///
+    /// # Returns
+    /// * `u32` - The proposal id
+    pub fn propose_payment(e: &Env, destination: Address, amount: i128) -> u32 {
+        Self::require_owner(e);
+
+        if amount <= 0 {
+            panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+        }
+        let policy = Self::get_approval_policy(e).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::InvalidApprovalPolicy);
+        });
+
+        let now = e.ledger().timestamp();
+        let proposal = PaymentProposal {
+            destination: destination.clone(),
+            amount,
+            approvals: Vec::new(e),
+            created_at: now,
+            expires_at: now + policy.proposal_ttl,
+            status: ProposalStatus::Pending,
+        };
+        let proposal_id = Self::get_proposal_count(e);
+        e.storage()
+            .persistent()
+            .set(&DataKey::Proposal(proposal_id), &proposal);
+        e.storage()
+            .instance()
+            .set(&DataKey::ProposalCount, &(proposal_id + 1));
+
+        Self::publish_audited(
+            e,
+            (symbol_short!("proposed"), destination),
+            (amount, proposal_id, proposal.expires_at),
+        );
+        proposal_id
+    }
+
+    /// Approve a pending payment proposal
+    ///
+    /// # Arguments
+    /// * `proposal_id` - The proposal to approve
+    /// * `signer` - A signer of the approval policy
+    pub fn approve_payment(e: &Env, proposal_id: u32, signer: Address) {
+        signer.require_auth();
+
+        let policy = Self::get_approval_policy(e).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::InvalidApprovalPolicy);
+        });
+        if !policy.signers.contains(&signer) {
+            panic_with_error!(e, HealthAidWalletError::NotApprover);
+        }
+
+        let mut proposal = Self::get_pending_proposal(e, proposal_id);
+        if proposal.approvals.contains(&signer) {
+            panic_with_error!(e, HealthAidWalletError::AlreadyApproved);
+        }
+        proposal.approvals.push_back(signer.clone());
+        e.storage()
+            .persistent()
+            .set(&DataKey::Proposal(proposal_id), &proposal);
+
+        Self::publish_audited(e, (symbol_short!("approved"), signer), proposal_id);
+    }
+
+    /// Execute a payment proposal approved by enough co-signers
+    pub fn execute_payment(e: &Env, proposal_id: u32) {
+        Self::require_owner(e);
+
+        let policy = Self::get_approval_policy(e).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::InvalidApprovalPolicy);
+        });
+        let mut proposal = Self::get_pending_proposal(e, proposal_id);
+
+        // Approvals from signers removed since then no longer count
+        let approvals = proposal
+            .approvals
+            .iter()
+            .filter(|signer| policy.signers.contains(signer))
+            .count() as u32;
+        if approvals < policy.required_approvals {
+            panic_with_error!(e, HealthAidWalletError::InsufficientApprovals);
+        }
+
+        proposal.status = ProposalStatus::Executed;
+        e.storage()
+            .persistent()
+            .set(&DataKey::Proposal(proposal_id), &proposal);
+
+        Self::settle_payments(
+            e,
+            &vec![e, (proposal.destination, proposal.amount)],
+            None,
+            true,
+        );
+    }
+
+    /// Cancel a pending payment proposal
+    pub fn cancel_proposal(e: &Env, proposal_id: u32) {
+        Self::require_owner(e);
+
+        let mut proposal = Self::get_proposal(e, proposal_id).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::ProposalNotFound);
+        });
+        if proposal.status != ProposalStatus::Pending {
+            panic_with_error!(e, HealthAidWalletError::InvalidProposalState);
+        }
+
+        proposal.status = ProposalStatus::Cancelled;
+        e.storage()
+            .persistent()
+            .set(&DataKey::Proposal(proposal_id), &proposal);
+
+        Self::publish_audited(e, (symbol_short!("cancelled"),), proposal_id);
+    }
+
+    /// Get a payment proposal
+    pub fn get_proposal(e: &Env, proposal_id: u32) -> Option<PaymentProposal> {
+        e.storage()
+            .persistent()
+            .get(&DataKey::Proposal(proposal_id))
+    }
+
+    /// Get the number of payment proposals created
+    pub fn get_proposal_count(e: &Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&DataKey::ProposalCount)
+            .unwrap_or(0)
+    }
+
+    /// Pay a healthcare provider through escrow
+    ///
+    /// The payment is withdrawn from the vault and held by the wallet until
+    /// the owner confirms the service or `DISPUTE_WINDOW` elapses. Within the
+    /// window the owner can dispute it instead, leaving the decision to the
+    /// arbiter appointed in the provider registry.
+    ///
+    /// # Arguments
+    /// * `destination` - Healthcare provider address
+    /// * `amount` - Amount to pay
+    ///
+    /// # Returns
+    /// * `u32` - The escrow id
+    pub fn pay_escrow(e: &Env, destination: Address, amount: i128) -> u32 {
+        Self::require_owner(e);
+
+        let principal = Self::get_principal(e);
+        let harvested_yield = Self::get_harvested_yield(e);
+        let (fee, treasury) =
+            Self::fund_payments(e, &vec![e, (destination.clone(), amount)], false);
+
+        let now = e.ledger().timestamp();
+        let escrow = EscrowPayment {
+            provider: destination.clone(),
+            amount,
+            fee,
+            cost_basis: principal - Self::get_principal(e),
+            harvested_yield: harvested_yield - Self::get_harvested_yield(e),
+            treasury,
+            created_at: now,
+            release_after: now + DISPUTE_WINDOW,
+            status: EscrowStatus::Pending,
+        };
+        let escrow_id = Self::get_escrow_count(e);
+        e.storage()
+            .persistent()
+            .set(&DataKey::Escrow(escrow_id), &escrow);
+        e.storage()
+            .instance()
+            .set(&DataKey::EscrowCount, &(escrow_id + 1));
+        Self::set_escrowed_total(e, Self::get_escrowed_total(e) + amount + fee);
+
+        Self::publish_audited(
+            e,
+            (symbol_short!("escrowed"), destination),
+            (amount, escrow_id, escrow.release_after),
+        );
+        escrow_id
+    }
+
+    /// Confirm the service and release an escrowed payment to the provider
+    pub fn confirm_service(e: &Env, escrow_id: u32) {
+        Self::require_owner(e);
+        Self::require_not_frozen(e);
+
+        let escrow = Self::get_escrow_or_panic(e, escrow_id);
+        if escrow.status != EscrowStatus::Pending {
+            panic_with_error!(e, HealthAidWalletError::InvalidEscrowState);
+        }
+        Self::release_escrow_payment(e, escrow_id, escrow);
+    }
+
+    /// Release an escrowed payment once its dispute window has elapsed
+    ///
+    /// Anyone can call this, typically the provider.
+    pub fn release_escrow(e: &Env, escrow_id: u32) {
+        Self::require_not_frozen(e);
+
+        let escrow = Self::get_escrow_or_panic(e, escrow_id);
+        if escrow.status != EscrowStatus::Pending {
+            panic_with_error!(e, HealthAidWalletError::InvalidEscrowState);
+        }
+        if e.ledger().timestamp() < escrow.release_after {
+            panic_with_error!(e, HealthAidWalletError::DisputeWindowOpen);
+        }
+        Self::release_escrow_payment(e, escrow_id, escrow);
+    }
+
+    /// Dispute an escrowed payment within its dispute window
+    pub fn dispute_payment(e: &Env, escrow_id: u32) {
+        Self::require_owner(e);
+
+        let mut escrow = Self::get_escrow_or_panic(e, escrow_id);
+        if escrow.status != EscrowStatus::Pending {
+            panic_with_error!(e, HealthAidWalletError::InvalidEscrowState);
+        }
+        if e.ledger().timestamp() >= escrow.release_after {
+            panic_with_error!(e, HealthAidWalletError::DisputeWindowClosed);
+        }
+
+        escrow.status = EscrowStatus::Disputed;
+        e.storage()
+            .persistent()
+            .set(&DataKey::Escrow(escrow_id), &escrow);
+        Self::publish_audited(e, (symbol_short!("disputed"), escrow.provider), escrow_id);
+    }
+
+    /// Resolve a disputed escrowed payment
+    ///
+    /// Only the arbiter appointed in the provider registry can call this.
+    ///
+    /// # Arguments
+    /// * `escrow_id` - The disputed escrow
+    /// * `release` - Release the payment to the provider, or refund it to the wallet
+    pub fn resolve_dispute(e: &Env, escrow_id: u32, release: bool) {
+        let registry_client = ProviderRegistryClient::new(e, &Self::get_registry_address(e));
+        let arbiter = registry_client.get_arbiter().unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::Unauthorized);
+        });
+        arbiter.require_auth();
+
+        let escrow = Self::get_escrow_or_panic(e, escrow_id);
+        if escrow.status != EscrowStatus::Disputed {
+            panic_with_error!(e, HealthAidWalletError::InvalidEscrowState);
+        }
+
+        if release {
+            Self::release_escrow_payment(e, escrow_id, escrow);
+        } else {
+            Self::refund_escrow_payment(e, escrow_id, escrow);
+        }
+    }
+
+    /// Get an escrowed payment
+    pub fn get_escrow(e: &Env, escrow_id: u32) -> Option<EscrowPayment> {
+        e.storage().persistent().get(&DataKey::Escrow(escrow_id))
+    }
+
+    /// Get the number of escrowed payments created
+    pub fn get_escrow_count(e: &Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&DataKey::EscrowCount)
+            .unwrap_or(0)
+    }
+
+    /// Place a hold reserving up to `max_amount` for a provider until `expiry`
+    ///
+    /// The funds stay in the vault but are excluded from the spendable
+    /// balance, along with the platform fee on `max_amount`. The provider
+    /// captures the final amount with `capture`; the reservation lapses
+    /// automatically at `expiry`.
+    ///
+    /// # Arguments
+    /// * `provider` - Healthcare provider address
+    /// * `max_amount` - Maximum amount the provider can capture
+    /// * `expiry` - Time at which the hold lapses
+    ///
+    /// # Returns
+    /// * `u32` - The hold id
+    pub fn authorize_hold(e: &Env, provider: Address, max_amount: i128, expiry: u64) -> u32 {
+        Self::require_owner(e);
+
+        let now = e.ledger().timestamp();
+        if max_amount <= 0 {
+            panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+        }
+        if expiry <= now {
+            panic_with_error!(e, HealthAidWalletError::HoldExpired);
+        }
+        Self::sync_config(e);
+        Self::require_payments_active(e);
+        Self::require_not_frozen(e);
+
+        Self::require_below_approval_threshold(e, max_amount);
+
+        let registry_client = ProviderRegistryClient::new(e, &Self::get_registry_address(e));
+        Self::require_payable(e, &registry_client, &provider);
+        let fee = match Self::get_fee_config(e) {
+            Some(fee_config) => {
+                Self::payment_fee(&fee_config, &registry_client, &provider, max_amount)
+            }
+            None => 0,
+        };
+
+        // Drop lapsed holds so the active list stays short
+        let mut active_holds = Vec::new(e);
+        for hold_id in Self::get_active_holds(e).iter() {
+            if Self::get_hold_or_panic(e, hold_id).expiry > now {
+                active_holds.push_back(hold_id);
+            }
+        }
+        e.storage()
+            .instance()
+            .set(&DataKey::ActiveHolds, &active_holds);
+
+        if Self::get_spendable_balance(e) < max_amount + fee {
+            panic_with_error!(e, HealthAidWalletError::InsufficientBalance);
+        }
+
+        let hold_id = Self::get_hold_count(e);
+        let hold = Hold {
+            provider: provider.clone(),
+            max_amount,
+            fee,
+            expiry,
+            captured: 0,
+            status: HoldStatus::Active,
+        };
+        e.storage().persistent().set(&DataKey::Hold(hold_id), &hold);
+        e.storage()
+            .instance()
+            .set(&DataKey::HoldCount, &(hold_id + 1));
+        active_holds.push_back(hold_id);
+        e.storage()
+            .instance()
+            .set(&DataKey::ActiveHolds, &active_holds);
+
+        Self::publish_audited(
+            e,
+            (symbol_short!("hold"), provider),
+            (max_amount, hold_id, expiry),
+        );
+        hold_id
+    }
+
+    /// Capture the final amount of a hold, paying the provider
+    ///
+    /// Called by the hold's provider before expiry. The uncaptured remainder
+    /// is released.
+    ///
+    /// # Arguments
+    /// * `hold_id` - The hold to capture
+    /// * `amount` - Amount to pay, at most the hold's `max_amount`
+    pub fn capture(e: &Env, hold_id: u32, amount: i128) {
+        let mut hold = Self::get_hold_or_panic(e, hold_id);
+        hold.provider.require_auth();
+
+        if hold.status != HoldStatus::Active {
+            panic_with_error!(e, HealthAidWalletError::InvalidHoldState);
+        }
+        if e.ledger().timestamp() >= hold.expiry {
+            panic_with_error!(e, HealthAidWalletError::HoldExpired);
+        }
+        if amount > hold.max_amount {
+            panic_with_error!(e, HealthAidWalletError::ExceedsHold);
+        }
+
+        // Release the reservation before paying out of the spendable balance
+        hold.status = HoldStatus::Captured;
+        hold.captured = amount;
+        e.storage().persistent().set(&DataKey::Hold(hold_id), &hold);
+        Self::remove_active_hold(e, hold_id);
+
+        // The hold itself was checked against the approval threshold
+        Self::settle_payments(e, &vec![e, (hold.provider, amount)], None, true);
+    }
+
+    /// Release a hold without capturing it
+    ///
+    /// # Arguments
+    /// * `caller` - The wallet owner or the hold's provider
+    /// * `hold_id` - The hold to release
+    pub fn release_hold(e: &Env, caller: Address, hold_id: u32) {
+        let mut hold = Self::get_hold_or_panic(e, hold_id);
+        if caller == Self::get_user(e) {
+            Self::require_owner(e);
+        } else if caller == hold.provider {
+            caller.require_auth();
+        } else {
+            panic_with_error!(e, HealthAidWalletError::Unauthorized);
+        }
+
+        if hold.status != HoldStatus::Active {
+            panic_with_error!(e, HealthAidWalletError::InvalidHoldState);
+        }
+
+        hold.status = HoldStatus::Released;
+        e.storage().persistent().set(&DataKey::Hold(hold_id), &hold);
+        Self::remove_active_hold(e, hold_id);
+
+        Self::publish_audited(e, (symbol_short!("hold_rel"), hold.provider), hold_id);
+    }
+
+    /// Get a hold
+    pub fn get_hold(e: &Env, hold_id: u32) -> Option<Hold> {
+        e.storage().persistent().get(&DataKey::Hold(hold_id))
+    }
+
+    /// Get the number of holds placed
+    pub fn get_hold_count(e: &Env) -> u32 {
+        e.storage().instance().get(&DataKey::HoldCount).unwrap_or(0)
+    }
+
+    /// Get the balance reserved by active, unexpired holds, fees included
+    pub fn get_reserved_balance(e: &Env) -> i128 {
+        let now = e.ledger().timestamp();
+        let mut reserved: i128 = 0;
+        for hold_id in Self::get_active_holds(e).iter() {
+            let hold = Self::get_hold_or_panic(e, hold_id);
+            if hold.expiry > now {
+                reserved += hold.max_amount + hold.fee;
+            }
+        }
+        reserved
+    }
+
+    /// Get the balance available for payments, excluding reserved funds
+    pub fn get_spendable_balance(e: &Env) -> i128 {
+        Self::get_balance(e) - Self::get_reserved_balance(e)
+    }
+
+    /// Get the USDC held by the wallet for pending and disputed escrows,
+    /// including withheld fees
+    pub fn get_escrowed_total(e: &Env) -> i128 {
+        e.storage()
+            .instance()
+            .get(&DataKey::EscrowedTotal)
+            .unwrap_or(0)
+    }
+}
+
+impl HealthAidWallet {
+    /// Abort if the protocol guardian paused deposits
+    fn require_deposits_active(e: &Env) {
+        let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+        if config.is_deposits_paused() {
+            panic_with_error!(e, HealthAidWalletError::ProtocolPaused);
+        }
+    }
+
+    /// Abort if the protocol guardian paused payments
+    fn require_payments_active(e: &Env) {
+        let config = ProtocolConfigClient::new(e, &Self::get_protocol_config(e));
+        if config.is_payments_paused() {
+            panic_with_error!(e, HealthAidWalletError::ProtocolPaused);
+        }
+    }
+
+    /// Require the owner's authorization and record it as owner activity,
+    /// which cancels any pending beneficiary release
+    fn require_owner(e: &Env) -> Address {
+        let user = Self::get_user(e);
+        user.require_auth();
+        Self::record_owner_activity(e);
+        user
+    }
+
+    fn record_owner_activity(e: &Env) {
+        e.storage()
+            .instance()
+            .set(&DataKey::LastOwnerActivity, &e.ledger().timestamp());
+    }
+
+    /// Whether `wallet` is a registered wallet with the same owner and
+    /// protocol config as this one
+    fn is_sibling_wallet(e: &Env, wallet: &Address) -> bool {
+        if !Self::is_registered_wallet(e, wallet) {
+            return false;
+        }
+
+        let protocol_config = Self::get_protocol_config(e);
+        let other = HealthAidWalletClient::new(e, wallet);
+        let same_owner =
+            matches!(other.try_get_user(), Ok(Ok(owner)) if owner == Self::get_user(e));
+        let same_config = matches!(
+            other.try_get_protocol_config(),
+            Ok(Ok(config)) if config == protocol_config
+        );
+        same_owner && same_config
+    }
+
+    fn is_registered_wallet(e: &Env, wallet: &Address) -> bool {
+        ProtocolConfigClient::new(e, &Self::get_protocol_config(e)).is_registered_wallet(wallet)
+    }
+
+    fn require_open(e: &Env) {
+        if Self::is_closed(e) {
+            panic_with_error!(e, HealthAidWalletError::WalletClosed);
+        }
+    }
+
+    /// Redeem the full vault position, send all USDC held by the wallet to
+    /// `recipient` and mark the wallet closed
+    fn close_out(e: &Env, recipient: &Address) -> i128 {
+        Self::require_open(e);
+        Self::require_not_frozen(e);
+        if Self::get_escrowed_total(e) > 0 {
+            panic_with_error!(e, HealthAidWalletError::EscrowsPending);
+        }
+        if Self::get_reserved_balance(e) > 0 {
+            panic_with_error!(e, HealthAidWalletError::HoldsActive);
+        }
+
+        let shares = Self::get_shares(e);
+        if !Self::is_idle_only(e) && shares > 0 {
+            let expected = Self::amount_for_shares(e, shares);
+            Self::set_shares(e, 0);
+            Self::redeem_shares(e, shares, expected);
+        }
+
+        let amount = Self::held_usdc(e);
+        if amount > 0 {
+            Self::transfer_usdc(e, recipient, amount);
+        }
+
+        e.storage().instance().remove(&DataKey::Principal);
+        e.storage().instance().remove(&DataKey::HarvestedYield);
+        e.storage().instance().set(&DataKey::Closed, &true);
+        Self::record_transaction(e, TransactionKind::Withdrawal, recipient, amount, None);
+        amount
+    }
+
+    fn require_not_frozen(e: &Env) {
+        if Self::is_frozen(e) {
+            panic_with_error!(e, HealthAidWalletError::WalletFrozen);
+        }
+    }
+
+    /// Push USDC held by the wallet into the DeFindex vault
+    ///
+    /// A vault that cannot be reached or traps fails with `FailedToDeposit`;
+    /// a vault that refuses the deposit with its own error fails with
+    /// `VaultRejectedDeposit`.
+    ///
+    /// In idle-only mode the USDC stays in the wallet and no shares are minted.
+    fn deposit_into_vault(e: &Env, amount: i128, min_shares: i128) -> i128 {
+        // Idle-only wallets keep deposits as USDC and mint no shares
+        if Self::is_idle_only(e) {
+            if min_shares > 0 {
+                panic_with_error!(e, HealthAidWalletError::SlippageExceeded);
+            }
+            return 0;
+        }
+
+        let defindex_contract = Self::get_defindex_contract(e);
+
+        if let Some(allocation) = Self::try_asset_allocation(e) {
+            if Self::all_strategies_paused(&allocation) {
+                panic_with_error!(e, HealthAidWalletError::AllStrategiesPaused);
+            }
+        }
+
+        let deposit_args: Vec<Val> = vec![
+            e,
+            amount.into_val(e),
+            min_shares.into_val(e),
+            e.current_contract_address().into_val(e),
+            true.into_val(e),
+        ];
+
+        // The vault pulls the deposited USDC from the wallet
+        let usdc_token = Self::get_usdc_token(e);
+        e.authorize_as_current_contract(vec![
+            e,
+            InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: defindex_contract.clone().into(),
+                    fn_name: Symbol::new(e, "deposit"),
+                    args: deposit_args.into_val(e),
+                },
+                sub_invocations: vec![e],
+            }),
+            InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: usdc_token,
+                    fn_name: Symbol::new(e, "transfer"),
+                    args: vec![
+                        e,
+                        e.current_contract_address().into_val(e),
+                        defindex_contract.into_val(e),
+                        amount.into_val(e),
+                    ],
+                },
+                sub_invocations: vec![e],
+            }),
+        ]);
+
+        let shares = match e.try_invoke_contract::<i128, InvokeError>(
+            &defindex_contract,
+            &Symbol::new(e, "deposit"),
+            deposit_args.into_val(e),
+        ) {
+            Ok(Ok(shares)) => shares,
+            Err(Ok(InvokeError::Contract(_))) => {
+                panic_with_error!(e, HealthAidWalletError::VaultRejectedDeposit)
+            }
+            _ => panic_with_error!(e, HealthAidWalletError::FailedToDeposit),
+        };
+
+        if shares < min_shares {
+            panic_with_error!(e, HealthAidWalletError::SlippageExceeded);
+        }
+
+        Self::set_shares(e, Self::get_shares(e) + shares);
+        shares
+    }
+
+    /// Count a deposit towards the current year's contributions, enforcing
+    /// the protocol's yearly caps
+    fn record_contribution(e: &Env, funder: &Address, amount: i128) {
+        let (year, _) = civil_month(e.ledger().timestamp());
+        let mut contributions = Self::contributions_for_year(e, year);
+        let limits =
+            ProtocolConfigClient::new(e, &Self::get_protocol_config(e)).get_contribution_limits();
+
+        let (total, limit) = if *funder == Self::get_user(e) {
+            contributions.owner += amount;
+            (contributions.owner, limits.and_then(|l| l.owner_limit))
+        } else {
+            contributions.third_party += amount;
+            (
+                contributions.third_party,
+                limits.and_then(|l| l.third_party_limit),
+            )
+        };
+        if limit.is_some_and(|limit| total > limit) {
+            panic_with_error!(e, HealthAidWalletError::ContributionLimitExceeded);
+        }
+
+        e.storage()
+            .persistent()
+            .set(&DataKey::Contributions(year), &contributions);
+    }
+
+    fn set_shares(e: &Env, shares: i128) {
+        e.storage().instance().set(&DataKey::Shares, &shares);
+    }
+
+    fn add_principal(e: &Env, amount: i128) {
+        let principal = Self::get_principal(e) + amount;
+        e.storage().instance().set(&DataKey::Principal, &principal);
+    }
+
+    /// Reduce the principal proportionally to the part of `balance` withdrawn
+    fn reduce_principal(e: &Env, withdrawn: i128, balance: i128) {
+        let principal = Self::get_principal(e);
+        let harvested = Self::get_harvested_yield(e);
+        let (cost_basis, harvested_spent) = if withdrawn >= balance {
+            (principal, harvested)
+        } else {
+            (
+                principal * withdrawn / balance,
+                harvested * withdrawn / balance,
+            )
+        };
+        e.storage()
+            .instance()
+            .set(&DataKey::Principal, &(principal - cost_basis));
+        e.storage()
+            .instance()
+            .set(&DataKey::HarvestedYield, &(harvested - harvested_spent));
+    }
+
+    /// Yield kept by the owner at past harvests, spent alongside the
+    /// principal so that it is not donated from again
+    fn get_harvested_yield(e: &Env) -> i128 {
+        e.storage()
+            .instance()
+            .get(&DataKey::HarvestedYield)
+            .unwrap_or(0)
+    }
+
+    /// Validate and execute payments to approved providers
+    ///
+    /// `approved` is set when the co-signers already approved the payments,
+    /// lifting the approval policy's threshold.
+    fn settle_payments(
+        e: &Env,
+        payments: &Vec<(Address, i128)>,
+        memo: Option<PaymentMemo>,
+        approved: bool,
+    ) {
+        let (fees, treasury) = Self::fund_payments(e, payments, approved);
+        for (destination, amount) in payments.iter() {
+            Self::transfer_usdc(e, &destination, amount);
+            Self::record_transaction(
+                e,
+                TransactionKind::Payment,
+                &destination,
+                amount,
+                memo.clone(),
+            );
+        }
+        Self::collect_fee(e, treasury, fees);
+    }
+
+    /// Validate payments to approved providers and withdraw their total plus
+    /// platform fees from the vault into the wallet
+    ///
+    /// # Returns
+    /// * `(i128, Option<Address>)` - The fees owed and the treasury receiving them
+    fn fund_payments(
+        e: &Env,
+        payments: &Vec<(Address, i128)>,
+        approved: bool,
+    ) -> (i128, Option<Address>) {
+        let mut total: i128 = 0;
+        for (_, amount) in payments.iter() {
+            if amount <= 0 {
+                panic_with_error!(e, HealthAidWalletError::InvalidAmount);
+            }
+            total = total
+                .checked_add(amount)
+                .unwrap_or_else(|| panic_with_error!(e, HealthAidWalletError::InvalidAmount));
+        }
+        Self::sync_config(e);
+        Self::require_payments_active(e);
+        Self::require_not_frozen(e);
+        if !approved {
+            Self::require_below_approval_threshold(e, total);
+        }
+
+        let current_balance = Self::get_balance(e);
+
+        // Check if every destination is an approved provider
+        let registry_address = Self::get_registry_address(e);
+        let registry_client = ProviderRegistryClient::new(e, &registry_address);
+
+        for (destination, _) in payments.iter() {
+            Self::require_payable(e, &registry_client, &destination);
+        }
+
+        let fee_config = Self::get_fee_config(e);
+        let mut fees: i128 = 0;
+        if let Some(fee_config) = &fee_config {
+            for (destination, amount) in payments.iter() {
+                fees += Self::payment_fee(fee_config, &registry_client, &destination, amount);
+            }
+        }
+
+        let total_with_fees = total + fees;
+        if current_balance - Self::get_reserved_balance(e) < total_with_fees {
+            panic_with_error!(e, HealthAidWalletError::InsufficientBalance);
+        }
+
+        Self::withdraw_from_vault(e, total_with_fees);
+        Self::reduce_principal(e, total_with_fees, current_balance);
+
+        (fees, fee_config.map(|fee_config| fee_config.treasury))
+    }
+
+    /// Transfer platform fees held by the wallet to the treasury
+    fn collect_fee(e: &Env, treasury: Option<Address>, fees: i128) {
+        if let Some(treasury) = treasury {
+            if fees > 0 {
+                Self::transfer_usdc(e, &treasury, fees);
+                Self::record_transaction(e, TransactionKind::Fee, &treasury, fees, None);
+            }
+        }
+    }
+
+    /// Round-up owed on a payment under the current policy and monthly cap
+    fn round_up_amount(e: &Env, amount: i128) -> i128 {
+        let policy = match Self::get_round_up(e) {
+            Some(policy) => policy,
+            None => return 0,
+        };
+        let remainder = amount % policy.increment;
+        if remainder == 0 {
+            return 0;
+        }
+
+        let (year, month) = civil_month(e.ledger().timestamp());
+        let cap_left = policy.monthly_cap - Self::get_round_up_donated(e, year, month);
+        (policy.increment - remainder)
+            .min(amount)
+            .min(cap_left)
+            .max(0)
+    }
+
+    /// Settle a single owner payment and donate its round-up
+    fn settle_rounded_up(e: &Env, destination: Address, amount: i128, memo: Option<PaymentMemo>) {
+        // The round-up leaves the wallet with the payment
+        Self::require_below_approval_threshold(e, amount + Self::round_up_amount(e, amount));
+        Self::settle_payments(e, &vec![e, (destination, amount)], memo, false);
+        Self::donate_round_up(e, amount);
+    }
+
+    /// Donate the round-up on a payment, skipped if the spendable balance
+    /// does not cover it
+    fn donate_round_up(e: &Env, amount: i128) {
+        let round_up = Self::round_up_amount(e, amount);
+        if round_up == 0 || Self::get_spendable_balance(e) < round_up {
+            return;
+        }
+        let policy = Self::get_round_up(e).expect("Round-up policy should be set");
+
+        let current_balance = Self::get_balance(e);
+        Self::withdraw_from_vault(e, round_up);
+        Self::reduce_principal(e, round_up, current_balance);
+        Self::donate(e, &policy.recipient, round_up);
+
+        let (year, month) = civil_month(e.ledger().timestamp());
+        let donated = Self::get_round_up_donated(e, year, month) + round_up;
+        e.storage()
+            .persistent()
+            .set(&DataKey::RoundUpDonated(year, month), &donated);
+    }
+
+    /// Transfer a donation held by the wallet and count it towards the total
+    fn donate(e: &Env, recipient: &Address, amount: i128) {
+        Self::transfer_usdc(e, recipient, amount);
+        Self::record_transaction(e, TransactionKind::Donation, recipient, amount, None);
+
+        let total = Self::get_total_donated(e) + amount;
+        e.storage().instance().set(&DataKey::TotalDonated, &total);
+    }
+
+    fn set_addresses(e: &Env, addresses: &ProtocolAddresses) {
+        e.storage()
+            .instance()
+            .set(&DataKey::RegistryAddress, &addresses.registry);
+        e.storage()
+            .instance()
+            .set(&DataKey::UsdcToken, &addresses.usdc_token);
+        e.storage()
+            .instance()
+            .set(&DataKey::DefindexContract, &addresses.defindex_vault);
+    }
+
+    /// Abort if switching to `addresses` would strand vault shares or USDC
+    fn require_position_movable(e: &Env, addresses: &ProtocolAddresses) {
+        if !Self::is_position_movable(e, addresses) {
+            panic_with_error!(e, HealthAidWalletError::PositionNotEmpty);
+        }
+    }
+
+    /// Whether switching to `addresses` leaves no shares in the old vault and
+    /// no USDC in the old token behind
+    fn is_position_movable(e: &Env, addresses: &ProtocolAddresses) -> bool {
+        let vault_changes = addresses.defindex_vault != Self::get_defindex_contract(e);
+        let usdc_changes = addresses.usdc_token != Self::get_usdc_token(e);
+        if (vault_changes || usdc_changes) && Self::get_shares(e) > 0 {
+            return false;
+        }
+        if usdc_changes {
+            let usdc = token::Client::new(e, &Self::get_usdc_token(e));
+            if usdc.balance(&e.current_contract_address()) > 0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Abort unless `destination` is approved in the registry and permitted by
+    /// the wallet's blocklist and allowlist mode
+    fn require_payable(e: &Env, registry_client: &ProviderRegistryClient, destination: &Address) {
+        if !registry_client.is_provider(destination) {
+            panic_with_error!(e, HealthAidWalletError::DestinationNotAllowed);
+        }
+        if Self::is_blocked(e, destination.clone()) {
+            panic_with_error!(e, HealthAidWalletError::ProviderBlocked);
+        }
+        if Self::is_allowlist_mode(e) && !Self::is_favourite(e, destination.clone()) {
+            panic_with_error!(e, HealthAidWalletError::ProviderNotFavourite);
+        }
+    }
+
+    fn is_allowed_by_wallet(e: &Env, destination: &Address) -> bool {
+        !Self::is_blocked(e, destination.clone())
+            && (!Self::is_allowlist_mode(e) || Self::is_favourite(e, destination.clone()))
+    }
+
+    /// Abort if the approval policy requires co-signers for `amount`
+    fn require_below_approval_threshold(e: &Env, amount: i128) {
+        if Self::exceeds_approval_threshold(e, amount) {
+            panic_with_error!(e, HealthAidWalletError::ApprovalRequired);
+        }
+    }
+
+    fn exceeds_approval_threshold(e: &Env, amount: i128) -> bool {
+        Self::get_approval_policy(e).is_some_and(|policy| amount > policy.threshold_amount)
+    }
+
+    /// Require the auth of `required_approvals` distinct signers of `policy`
+    fn require_co_signers(e: &Env, policy: &ApprovalPolicy, co_signers: &Vec<Address>) {
+        if Self::has_duplicates(co_signers) {
+            panic_with_error!(e, HealthAidWalletError::NotApprover);
+        }
+        for co_signer in co_signers.iter() {
+            if !policy.signers.contains(&co_signer) {
+                panic_with_error!(e, HealthAidWalletError::NotApprover);
+            }
+            co_signer.require_auth();
+        }
+        if co_signers.len() < policy.required_approvals {
+            panic_with_error!(e, HealthAidWalletError::InsufficientApprovals);
+        }
+    }
+
+    fn has_duplicates(addresses: &Vec<Address>) -> bool {
+        addresses
+            .iter()
+            .enumerate()
+            .any(|(i, address)| addresses.iter().skip(i + 1).any(|other| other == address))
+    }
+
+    /// Get a proposal that can still be approved or executed
+    fn get_pending_proposal(e: &Env, proposal_id: u32) -> PaymentProposal {
+        let proposal = Self::get_proposal(e, proposal_id).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::ProposalNotFound);
+        });
+        if proposal.status != ProposalStatus::Pending {
+            panic_with_error!(e, HealthAidWalletError::InvalidProposalState);
+        }
+        if e.ledger().timestamp() >= proposal.expires_at {
+            panic_with_error!(e, HealthAidWalletError::ProposalExpired);
+        }
+        proposal
+    }
+
+    fn get_escrow_or_panic(e: &Env, escrow_id: u32) -> EscrowPayment {
+        Self::get_escrow(e, escrow_id).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::EscrowNotFound);
+        })
+    }
+
+    fn get_hold_or_panic(e: &Env, hold_id: u32) -> Hold {
+        Self::get_hold(e, hold_id).unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::HoldNotFound);
+        })
+    }
+
+    fn get_active_holds(e: &Env) -> Vec<u32> {
+        e.storage()
+            .instance()
+            .get(&DataKey::ActiveHolds)
+            .unwrap_or(Vec::new(e))
+    }
+
+    fn remove_active_hold(e: &Env, hold_id: u32) {
+        let mut active_holds = Self::get_active_holds(e);
+        if let Some(index) = active_holds.first_index_of(hold_id) {
+            active_holds.remove(index);
+        }
+        e.storage()
+            .instance()
+            .set(&DataKey::ActiveHolds, &active_holds);
+    }
+
+    fn set_escrowed_total(e: &Env, total: i128) {
+        e.storage().instance().set(&DataKey::EscrowedTotal, &total);
+    }
+
+    /// Pay an escrowed payment and its withheld fee out of the wallet
+    fn release_escrow_payment(e: &Env, escrow_id: u32, mut escrow: EscrowPayment) {
+        escrow.status = EscrowStatus::Released;
+        e.storage()
+            .persistent()
+            .set(&DataKey::Escrow(escrow_id), &escrow);
+        Self::set_escrowed_total(e, Self::get_escrowed_total(e) - escrow.amount - escrow.fee);
+
+        Self::transfer_usdc(e, &escrow.provider, escrow.amount);
+        Self::record_transaction(
+            e,
+            TransactionKind::Payment,
+            &escrow.provider,
+            escrow.amount,
+            None,
+        );
+        Self::collect_fee(e, escrow.treasury, escrow.fee);
+    }
+
+    /// Return an escrowed payment and its withheld fee to the wallet's balance
+    fn refund_escrow_payment(e: &Env, escrow_id: u32, mut escrow: EscrowPayment) {
+        escrow.status = EscrowStatus::Refunded;
+        e.storage()
+            .persistent()
+            .set(&DataKey::Escrow(escrow_id), &escrow);
+
+        let refund = escrow.amount + escrow.fee;
+        Self::set_escrowed_total(e, Self::get_escrowed_total(e) - refund);
+
+        Self::sync_config(e);
+        Self::deposit_into_vault(e, refund, 0);
+        // The refund returns the yield spent on the payment as yield
+        Self::add_principal(e, escrow.cost_basis);
+        e.storage().instance().set(
+            &DataKey::HarvestedYield,
+            &(Self::get_harvested_yield(e) + escrow.harvested_yield),
+        );
+        Self::record_transaction(e, TransactionKind::Refund, &escrow.provider, refund, None);
+    }
+
+    /// USDC held by the wallet outside of escrows
+    fn held_usdc(e: &Env) -> i128 {
+        let usdc = token::Client::new(e, &Self::get_usdc_token(e));
+        usdc.balance(&e.current_contract_address()) - Self::get_escrowed_total(e)
+    }
+
+    /// Get the platform fee configured in the protocol config
+    fn get_fee_config(e: &Env) -> Option<FeeConfig> {
+        ProtocolConfigClient::new(e, &Self::get_protocol_config(e)).get_fee_config()
+    }
+
+    /// Compute the platform fee owed on a payment of `amount` to `destination`
+    fn payment_fee(
+        fee_config: &FeeConfig,
+        registry_client: &ProviderRegistryClient,
+        destination: &Address,
+        amount: i128,
+    ) -> i128 {
+        if !fee_config.exempt_categories.is_empty() {
+            if let Some(category) = registry_client.get_provider_category(destination) {
+                if fee_config.exempt_categories.contains(&category) {
+                    return 0;
+                }
+            }
+        }
+
+        let fee = amount * fee_config.bps as i128 / BPS_DENOMINATOR + fee_config.flat;
+        match fee_config.max_fee {
+            Some(max_fee) => fee.min(max_fee),
+            None => fee,
+        }
+    }
+
+    /// Call a read-only DeFindex vault function, returning `None` if it fails
+    fn try_read_vault<T: TryFromVal<Env, Val>>(e: &Env, func: &str, args: Vec<Val>) -> Option<T> {
+        match e.try_invoke_contract::<T, InvokeError>(
+            &Self::get_defindex_contract(e),
+            &Symbol::new(e, func),
+            args,
+        ) {
+            Ok(Ok(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn try_get_balance(e: &Env) -> Option<i128> {
+        if Self::is_idle_only(e) {
+            return Some(Self::held_usdc(e));
+        }
+
+        let shares: i128 = Self::try_read_vault(
+            e,
+            "balance",
+            vec![e, e.current_contract_address().into_val(e)],
+        )?;
+        Self::try_read_vault(
+            e,
+            "get_asset_amounts_per_shares",
+            vec![e, shares.into_val(e)],
+        )
+    }
+
+    /// Read the allocation of the wallet's USDC, falling back to the vault's
+    /// first asset
+    fn try_asset_allocation(e: &Env) -> Option<CurrentAssetInvestmentAllocation> {
+        let allocations: Vec<CurrentAssetInvestmentAllocation> =
+            Self::try_read_vault(e, "fetch_total_managed_funds", vec![e])?;
+        let usdc_token = Self::get_usdc_token(e);
+        allocations
+            .iter()
+            .find(|allocation| allocation.asset == usdc_token)
+            .or_else(|| allocations.get(0))
+    }
+
+    fn any_strategy_paused(allocation: &CurrentAssetInvestmentAllocation) -> bool {
+        allocation
+            .strategy_allocations
+            .iter()
+            .any(|strategy| strategy.paused)
+    }
+
+    /// Funds the vault can pay out: idle funds plus the active strategies
+    fn withdrawable_amount(allocation: &CurrentAssetInvestmentAllocation) -> i128 {
+        allocation
+            .strategy_allocations
+            .iter()
+            .filter(|strategy| !strategy.paused)
+            .fold(allocation.idle_amount, |total, strategy| {
+                total + strategy.amount
+            })
+    }
+
+    fn all_strategies_paused(allocation: &CurrentAssetInvestmentAllocation) -> bool {
+        !allocation.strategy_allocations.is_empty()
+            && allocation
+                .strategy_allocations
+                .iter()
+                .all(|strategy| strategy.paused)
+    }
+
+    /// Value `shares` of the DeFindex vault in USDC
+    fn amount_for_shares(e: &Env, shares: i128) -> i128 {
+        Self::try_read_vault(
+            e,
+            "get_asset_amounts_per_shares",
+            vec![e, shares.into_val(e)],
+        )
+        .unwrap_or_else(|| {
+            panic_with_error!(e, HealthAidWalletError::FailedToGetBalance);
+        })
+    }
+
+    /// Number of vault shares to redeem to withdraw at least `amount` of USDC
+    fn shares_for_amount(e: &Env, amount: i128, total_amount: i128) -> i128 {
+        if total_amount == 0 {
+            return 0;
+        }
+        let total_supply = Self::get_total_supply(e);
+
+        // Round up so the redeemed shares cover at least `amount`
+        (amount * total_supply + total_amount - 1) / total_amount
+    }
+
+    /// Withdraw `amount` of USDC from the DeFindex vault into the wallet
+    ///
+    /// Paused strategies cannot be unwound, so while any strategy is paused the
+    /// withdrawal must be covered by the vault's idle funds.
+    fn withdraw_from_vault(e: &Env, amount: i128) {
+        if Self::is_idle_only(e) {
+            return;
+        }
+
+        // The vault pays out of idle funds first, then out of the strategies
+        // that are still active
+        let allocation = Self::get_asset_allocation(e);
+        if Self::any_strategy_paused(&allocation) && Self::withdrawable_amount(&allocation) < amount
+        {
+            panic_with_error!(e, HealthAidWalletError::InsufficientIdleFunds);
+        }
+
+        let shares_needed = Self::shares_for_amount(e, amount, allocation.total_amount);
+        Self::set_shares(e, Self::get_shares(e) - shares_needed);
+        Self::redeem_shares(e, shares_needed, amount);
+    }
+
+    /// Redeem `shares` from the DeFindex vault for at least `min_amount` of USDC
+    fn redeem_shares(e: &Env, shares: i128, min_amount: i128) -> i128 {
+        let defindex_contract = Self::get_defindex_contract(e);
+
+        let withdraw_args: Vec<Val> = vec![
+            e,
+            shares.into_val(e),
+            vec![e, min_amount].into_val(e),
+            e.current_contract_address().into_val(e),
+        ];
+
+        // Add authorization for DeFindex withdraw call
+        e.authorize_as_current_contract(vec![
+            e,
+            InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: defindex_contract.clone().into(),
+                    fn_name: Symbol::new(e, "withdraw"),
+                    args: withdraw_args.into_val(e),
+                },
+                sub_invocations: vec![e],
+            }),
+        ]);
+
+        e.try_invoke_contract::<i128, InvokeError>(
+            &defindex_contract,
+            &Symbol::new(e, "withdraw"),
             withdraw_args.into_val(e),
         )
         .unwrap_or_else(|_| {
             panic_with_error!(e, HealthAidWalletError::FailedToWithdraw);
         })
-        .unwrap();
+        .unwrap_or_else(|_| {
+            panic_with_error!(e, HealthAidWalletError::FailedToWithdraw);
+        })
+    }
 
-        //transfer to destination
+    /// Transfer USDC held by the wallet to `destination`
+    fn transfer_usdc(e: &Env, destination: &Address, amount: i128) {
         let usdc_token = Self::get_usdc_token(e);
         let client = token::Client::new(e, &usdc_token);
 
         e.authorize_as_current_contract(vec![
-            &e,
+            e,
             InvokerContractAuthEntry::Contract(SubContractInvocation {
                 context: ContractContext {
                     contract: usdc_token.clone().into(),
-
-                    fn_name: Symbol::new(&e, "transfer"),
-
+                    fn_name: Symbol::new(e, "transfer"),
                     args: vec![
                         e,
                         e.current_contract_address().into_val(e),
@@ -309,10 +2981,86 @@ impl HealthAidWallet {
                         amount.into_val(e),
                     ],
                 },
-
-                sub_invocations: vec![&e],
+                sub_invocations: vec![e],
             }),
         ]);
-        client.transfer(&e.current_contract_address(), &destination, &amount);
+        client.transfer(&e.current_contract_address(), destination, &amount);
+    }
+
+    /// Emit an event and extend the audit chain with it
+    fn publish_audited<T, D>(e: &Env, topics: T, data: D)
+    where
+        T: IntoVal<Env, Vec<Val>>,
+        D: IntoVal<Env, Val>,
+    {
+        let topics: Vec<Val> = topics.into_val(e);
+        let data: Val = data.into_val(e);
+
+        let mut entry = Bytes::from_array(e, &Self::get_audit_head(e).to_array());
+        entry.append(&topics.clone().to_xdr(e));
+        entry.append(&data.to_xdr(e));
+        let head = e.crypto().sha256(&entry).to_bytes();
+        e.storage().instance().set(&DataKey::AuditHead, &head);
+
+        e.events().publish(topics, data);
+    }
+
+    /// Append an entry to the transaction history and emit the matching event
+    fn record_transaction(
+        e: &Env,
+        kind: TransactionKind,
+        counterparty: &Address,
+        amount: i128,
+        memo: Option<PaymentMemo>,
+    ) {
+        let index = Self::get_history_count(e);
+        let record = TransactionRecord {
+            kind,
+            counterparty: counterparty.clone(),
+            amount,
+            timestamp: e.ledger().timestamp(),
+        };
+
+        e.storage()
+            .persistent()
+            .set(&DataKey::History(index), &record);
+        if let Some(memo) = &memo {
+            e.storage()
+                .persistent()
+                .set(&DataKey::PaymentMemo(index), memo);
+        }
+        e.storage()
+            .instance()
+            .set(&DataKey::HistoryCount, &(index + 1));
+
+        let topic = match kind {
+            TransactionKind::Deposit => symbol_short!("deposit"),
+            TransactionKind::Payment => symbol_short!("pay"),
+            TransactionKind::Donation => symbol_short!("donate"),
+            TransactionKind::Fee => symbol_short!("fee"),
+            TransactionKind::Withdrawal => symbol_short!("withdraw"),
+            TransactionKind::Refund => symbol_short!("refund"),
+        };
+        Self::publish_audited(e, (topic, counterparty.clone()), (amount, index, memo));
+    }
+}
+
+/// Calendar year and month (1 to 12, UTC) of a unix timestamp
+///
+/// Uses the days-to-civil conversion from Howard Hinnant's date algorithms.
+fn civil_month(timestamp: u64) -> (u32, u32) {
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let year = year_of_era + era * 400;
+    // Years in this scheme start in March
+    if month_index >= 10 {
+        ((year + 1) as u32, (month_index - 9) as u32)
+    } else {
+        (year as u32, (month_index + 3) as u32)
     }
 }
@@ -1,11 +1,287 @@
 #![cfg(test)]
+extern crate std;
 
 use super::*;
+use ed25519_dalek::{Signer, SigningKey};
+use protocol_config::{
+    ContributionLimits, FeeConfig, PauseScope, ProtocolAddresses, ProtocolConfig,
+    ProtocolConfigClient, UPDATE_TIMELOCK,
+};
+use provider_registry::{ProviderRegistry, ProviderRegistryClient};
 use soroban_sdk::{
-    testutils::{Address as TestAddress, MockAuth, MockAuthInvoke},
-    Address, Env, IntoVal,
+    testutils::{Address as TestAddress, Events, Ledger, MockAuth, MockAuthInvoke},
+    token::{StellarAssetClient, TokenClient},
+    vec,
+    xdr::ToXdr,
+    Address, Bytes, BytesN, Env, IntoVal, String, Symbol, Vec,
 };
 
+/// Minimal stand-in for the DeFindex vault, mirroring the call shapes used by
+/// the wallet. Deposits pull the USDC from the depositor and mint shares at the
+/// current share price; withdrawals pay out of the vault's own USDC balance.
+mod mock_vault {
+    use crate::{CurrentAssetInvestmentAllocation, StrategyAllocation};
+    use soroban_sdk::{
+        contract, contracterror, contractimpl, contracttype, panic_with_error, token, vec, Address,
+        Env, Vec,
+    };
+
+    #[contracttype]
+    enum MockVaultKey {
+        Asset,
+        TotalSupply,
+        TotalFunds,
+        Shares(Address),
+        Paused,
+        Idle,
+        Strategies,
+    }
+
+    #[contracterror]
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+    #[repr(u32)]
+    pub enum MockVaultError {
+        StrategyPaused = 144,
+    }
+
+    #[contract]
+    pub struct MockVault;
+
+    #[contractimpl]
+    impl MockVault {
+        pub fn __constructor(e: &Env, asset: Address) {
+            e.storage().instance().set(&MockVaultKey::Asset, &asset);
+        }
+
+        pub fn deposit(e: &Env, amount: i128, _min: i128, from: Address, _invest: bool) -> i128 {
+            if Self::paused(e) {
+                panic_with_error!(e, MockVaultError::StrategyPaused);
+            }
+
+            let asset: Address = e.storage().instance().get(&MockVaultKey::Asset).unwrap();
+            token::Client::new(e, &asset).transfer(&from, &e.current_contract_address(), &amount);
+
+            let supply = Self::read_total_supply(e);
+            let funds = Self::total_funds(e);
+            let shares = if supply == 0 {
+                amount
+            } else {
+                amount * supply / funds
+            };
+
+            Self::set_shares(e, &from, Self::balance(e, from.clone()) + shares);
+            e.storage()
+                .instance()
+                .set(&MockVaultKey::TotalSupply, &(supply + shares));
+            e.storage()
+                .instance()
+                .set(&MockVaultKey::TotalFunds, &(funds + amount));
+            shares
+        }
+
+        pub fn withdraw(e: &Env, shares: i128, _min_amounts: Vec<i128>, to: Address) -> i128 {
+            let amount = Self::get_asset_amounts_per_shares(e, shares);
+
+            // Idle funds are used first, then the active strategies
+            let idle = Self::idle(e);
+            let strategies = Self::strategies(e);
+            let invested = Self::total_funds(e) - idle.min(Self::total_funds(e));
+            let active = strategies.iter().filter(|paused| !paused).count() as i128;
+            if active < strategies.len() as i128
+                && amount > idle + invested * active / strategies.len() as i128
+            {
+                panic_with_error!(e, MockVaultError::StrategyPaused);
+            }
+            e.storage()
+                .instance()
+                .set(&MockVaultKey::Idle, &(idle - amount).max(0));
+
+            Self::set_shares(e, &to, Self::balance(e, to.clone()) - shares);
+            e.storage().instance().set(
+                &MockVaultKey::TotalSupply,
+                &(Self::read_total_supply(e) - shares),
+            );
+            e.storage()
+                .instance()
+                .set(&MockVaultKey::TotalFunds, &(Self::total_funds(e) - amount));
+
+            let asset: Address = e.storage().instance().get(&MockVaultKey::Asset).unwrap();
+            token::Client::new(e, &asset).transfer(&e.current_contract_address(), &to, &amount);
+            amount
+        }
+
+        pub fn balance(e: &Env, id: Address) -> i128 {
+            e.storage()
+                .persistent()
+                .get(&MockVaultKey::Shares(id))
+                .unwrap_or(0)
+        }
+
+        pub fn get_asset_amounts_per_shares(e: &Env, shares: i128) -> i128 {
+            let supply = Self::read_total_supply(e);
+            if supply == 0 {
+                return 0;
+            }
+            shares * Self::total_funds(e) / supply
+        }
+
+        pub fn read_total_supply(e: &Env) -> i128 {
+            e.storage()
+                .instance()
+                .get(&MockVaultKey::TotalSupply)
+                .unwrap_or(0)
+        }
+
+        pub fn fetch_total_managed_funds(e: &Env) -> Vec<CurrentAssetInvestmentAllocation> {
+            let asset: Address = e.storage().instance().get(&MockVaultKey::Asset).unwrap();
+            let total_amount = Self::total_funds(e);
+            let idle_amount = Self::idle(e).min(total_amount);
+            let invested_amount = total_amount - idle_amount;
+
+            let strategies = Self::strategies(e);
+            let mut strategy_allocations = Vec::<StrategyAllocation>::new(e);
+            for paused in strategies.iter() {
+                strategy_allocations.push_back(StrategyAllocation {
+                    strategy_address: e.current_contract_address(),
+                    amount: invested_amount / strategies.len() as i128,
+                    paused,
+                });
+            }
+
+            vec![
+                e,
+                CurrentAssetInvestmentAllocation {
+                    asset,
+                    total_amount,
+                    idle_amount,
+                    invested_amount,
+                    strategy_allocations,
+                },
+            ]
+        }
+
+        /// Test helper setting the vault's strategies by their paused flag
+        pub fn set_strategies(e: &Env, strategies: Vec<bool>) {
+            e.storage()
+                .instance()
+                .set(&MockVaultKey::Strategies, &strategies);
+        }
+
+        /// Test helper setting the funds the vault keeps idle
+        pub fn set_idle(e: &Env, amount: i128) {
+            e.storage().instance().set(&MockVaultKey::Idle, &amount);
+        }
+
+        /// Test helper simulating yield (or losses) accrued by the strategies
+        pub fn accrue(e: &Env, amount: i128) {
+            e.storage()
+                .instance()
+                .set(&MockVaultKey::TotalFunds, &(Self::total_funds(e) + amount));
+        }
+
+        /// Test helper pausing the vault's strategies
+        pub fn set_paused(e: &Env, paused: bool) {
+            e.storage().instance().set(&MockVaultKey::Paused, &paused);
+        }
+
+        fn paused(e: &Env) -> bool {
+            e.storage()
+                .instance()
+                .get(&MockVaultKey::Paused)
+                .unwrap_or(false)
+        }
+
+        fn idle(e: &Env) -> i128 {
+            e.storage().instance().get(&MockVaultKey::Idle).unwrap_or(0)
+        }
+
+        fn strategies(e: &Env) -> Vec<bool> {
+            e.storage()
+                .instance()
+                .get(&MockVaultKey::Strategies)
+                .unwrap_or(Vec::new(e))
+        }
+
+        fn total_funds(e: &Env) -> i128 {
+            e.storage()
+                .instance()
+                .get(&MockVaultKey::TotalFunds)
+                .unwrap_or(0)
+        }
+
+        fn set_shares(e: &Env, id: &Address, shares: i128) {
+            e.storage()
+                .persistent()
+                .set(&MockVaultKey::Shares(id.clone()), &shares);
+        }
+    }
+}
+
+use mock_vault::{MockVault, MockVaultClient};
+
+struct WalletSetup<'a> {
+    user: Address,
+    provider: Address,
+    guardian: Address,
+    registry: ProviderRegistryClient<'a>,
+    usdc: StellarAssetClient<'a>,
+    vault: MockVaultClient<'a>,
+    config: ProtocolConfigClient<'a>,
+    wallet: HealthAidWalletClient<'a>,
+}
+
+/// Register a wallet wired to a real registry and protocol config, a Stellar
+/// asset standing in for USDC and the mock vault, with `provider` approved in
+/// the registry and the wallet registered in the protocol config.
+fn setup_wallet(env: &Env) -> WalletSetup<'_> {
+    env.mock_all_auths();
+
+    let user = Address::generate(env);
+    let admin = Address::generate(env);
+    let provider = Address::generate(env);
+    let guardian = Address::generate(env);
+
+    let registry_id = env.register(ProviderRegistry, (&admin,));
+    let registry = ProviderRegistryClient::new(env, &registry_id);
+    registry.add_provider(&provider);
+
+    let usdc_id = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    let vault_id = env.register(MockVault, (&usdc_id,));
+    let addresses = ProtocolAddresses {
+        registry: registry_id.clone(),
+        usdc_token: usdc_id.clone(),
+        defindex_vault: vault_id.clone(),
+    };
+    let config_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+
+    let wallet_id = env.register(HealthAidWallet, (&user, &config_id));
+    let config = ProtocolConfigClient::new(env, &config_id);
+    config.register_wallet(&wallet_id);
+
+    WalletSetup {
+        user,
+        provider,
+        guardian,
+        registry,
+        usdc: StellarAssetClient::new(env, &usdc_id),
+        vault: MockVaultClient::new(env, &vault_id),
+        config,
+        wallet: HealthAidWalletClient::new(env, &wallet_id),
+    }
+}
+
+/// Send USDC from the owner to the wallet, ready to be deposited
+fn fund_wallet(setup: &WalletSetup, amount: i128) {
+    setup.usdc.mint(&setup.user, &amount);
+    TokenClient::new(&setup.wallet.env, &setup.usdc.address).transfer(
+        &setup.user,
+        &setup.wallet.address,
+        &amount,
+    );
+}
+
 fn create_test_addresses(env: &Env) -> (Address, Address, Address, Address, Address, Address) {
     let user = Address::generate(env);
     let registry = Address::generate(env);
@@ -23,15 +299,32 @@ fn create_test_addresses(env: &Env) -> (Address, Address, Address, Address, Addr
     )
 }
 
+/// Register a wallet wired to an unpaused ProtocolConfig holding the given addresses
+fn register_wallet(
+    env: &Env,
+    user: &Address,
+    registry: &Address,
+    usdc_token: &Address,
+    defindex_contract: &Address,
+) -> Address {
+    let addresses = ProtocolAddresses {
+        registry: registry.clone(),
+        usdc_token: usdc_token.clone(),
+        defindex_vault: defindex_contract.clone(),
+    };
+    let protocol_config = env.register(
+        ProtocolConfig,
+        (&Address::generate(env), &Address::generate(env), &addresses),
+    );
+    env.register(HealthAidWallet, (user, &protocol_config))
+}
+
 #[test]
 fn test_constructor() {
     let env = Env::default();
     let (user, registry, usdc_token, defindex_contract, _, _) = create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     // Test that all addresses are set correctly
@@ -39,35 +332,40 @@ fn test_constructor() {
     assert_eq!(client.get_registry_address(), registry);
     assert_eq!(client.get_usdc_token(), usdc_token);
     assert_eq!(client.get_defindex_contract(), defindex_contract);
+    assert_eq!(client.get_config_version(), 1);
 }
 
 #[test]
 #[should_panic(expected = "Error(Contract, #1004)")]
 fn test_deposit_defindex_failure() {
     let env = Env::default();
-    let (user, registry, usdc_token, defindex_contract, _, _) = create_test_addresses(&env);
+    let (user, registry, _, defindex_contract, _, _) = create_test_addresses(&env);
+    let usdc_token = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = 1000i128;
 
+    // The wallet holds the USDC to deposit
+    env.mock_all_auths();
+    StellarAssetClient::new(&env, &usdc_token).mint(&contract_id, &amount);
+
     // Mock auth for the user
     env.mock_auths(&[MockAuth {
         address: &user,
         invoke: &MockAuthInvoke {
             contract: &contract_id,
             fn_name: "deposit",
-            args: (amount,).into_val(&env),
+            args: (amount, 0i128).into_val(&env),
             sub_invokes: &[],
         },
     }]);
 
     // This will fail because the defindex contract doesn't exist in test environment
-    client.deposit(&amount);
+    client.deposit(&amount, &0);
 }
 
 #[test]
@@ -76,10 +374,7 @@ fn test_deposit_invalid_amount_zero() {
     let env = Env::default();
     let (user, registry, usdc_token, defindex_contract, _, _) = create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = 0i128;
@@ -89,12 +384,12 @@ fn test_deposit_invalid_amount_zero() {
         invoke: &MockAuthInvoke {
             contract: &contract_id,
             fn_name: "deposit",
-            args: (amount,).into_val(&env),
+            args: (amount, 0i128).into_val(&env),
             sub_invokes: &[],
         },
     }]);
 
-    client.deposit(&amount);
+    client.deposit(&amount, &0);
 }
 
 #[test]
@@ -103,10 +398,7 @@ fn test_deposit_invalid_amount_negative() {
     let env = Env::default();
     let (user, registry, usdc_token, defindex_contract, _, _) = create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = -100i128;
@@ -116,12 +408,12 @@ fn test_deposit_invalid_amount_negative() {
         invoke: &MockAuthInvoke {
             contract: &contract_id,
             fn_name: "deposit",
-            args: (amount,).into_val(&env),
+            args: (amount, 0i128).into_val(&env),
             sub_invokes: &[],
         },
     }]);
 
-    client.deposit(&amount);
+    client.deposit(&amount, &0);
 }
 
 #[test]
@@ -131,10 +423,7 @@ fn test_deposit_unauthorized() {
     let (user, registry, usdc_token, defindex_contract, _, unauthorized) =
         create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = 1000i128;
@@ -145,12 +434,12 @@ fn test_deposit_unauthorized() {
         invoke: &MockAuthInvoke {
             contract: &contract_id,
             fn_name: "deposit",
-            args: (amount,).into_val(&env),
+            args: (amount, 0i128).into_val(&env),
             sub_invokes: &[],
         },
     }]);
 
-    client.deposit(&amount);
+    client.deposit(&amount, &0);
 }
 
 #[test]
@@ -159,10 +448,7 @@ fn test_pay_to_unapproved_provider() {
     let env = Env::default();
     let (user, registry, usdc_token, defindex_contract, provider, _) = create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = 1000i128;
@@ -187,10 +473,7 @@ fn test_pay_invalid_amount_zero() {
     let env = Env::default();
     let (user, registry, usdc_token, defindex_contract, provider, _) = create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = 0i128;
@@ -214,10 +497,7 @@ fn test_pay_invalid_amount_negative() {
     let env = Env::default();
     let (user, registry, usdc_token, defindex_contract, provider, _) = create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = -100i128;
@@ -242,10 +522,7 @@ fn test_pay_unauthorized() {
     let (user, registry, usdc_token, defindex_contract, provider, unauthorized) =
         create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = 1000i128;
@@ -270,10 +547,7 @@ fn test_pay_insufficient_balance() {
     let env = Env::default();
     let (user, registry, usdc_token, defindex_contract, provider, _) = create_test_addresses(&env);
 
-    let contract_id = env.register(
-        HealthAidWallet,
-        (&user, &registry, &usdc_token, &defindex_contract),
-    );
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
     let client = HealthAidWalletClient::new(&env, &contract_id);
 
     let amount = 1000i128;
@@ -291,3 +565,2501 @@ fn test_pay_insufficient_balance() {
     // This will fail because the defindex contract doesn't exist (FailedToGetBalance)
     client.pay(&provider, &amount);
 }
+
+#[test]
+fn test_deposit_from_funder() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let funder = Address::generate(&env);
+    setup.usdc.mint(&funder, &5000);
+
+    assert_eq!(setup.wallet.deposit_from(&funder, &2000, &2000), 2000);
+
+    // The funder's USDC moves through the wallet into the vault
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&funder), 3000);
+    assert_eq!(usdc.balance(&setup.wallet.address), 0);
+    assert_eq!(usdc.balance(&setup.vault.address), 2000);
+    assert_eq!(setup.vault.balance(&setup.wallet.address), 2000);
+    assert_eq!(setup.wallet.get_balance(), 2000);
+
+    let history = setup.wallet.get_history(&0, &10);
+    assert_eq!(history.len(), 1);
+    let record = history.get(0).unwrap();
+    assert_eq!(record.kind, TransactionKind::Deposit);
+    assert_eq!(record.counterparty, funder);
+    assert_eq!(record.amount, 2000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1003)")]
+fn test_deposit_from_invalid_amount() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let funder = Address::generate(&env);
+
+    setup.wallet.deposit_from(&funder, &0, &0);
+}
+
+#[test]
+fn test_pay_records_history() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.pay(&setup.provider, &400);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.provider), 400);
+    assert_eq!(setup.wallet.get_balance(), 600);
+
+    let history = setup.wallet.get_history(&0, &10);
+    assert_eq!(setup.wallet.get_history_count(), 2);
+    assert_eq!(history.get(0).unwrap().counterparty, setup.user);
+    let payment = history.get(1).unwrap();
+    assert_eq!(payment.kind, TransactionKind::Payment);
+    assert_eq!(payment.counterparty, setup.provider);
+    assert_eq!(payment.amount, 400);
+}
+
+#[test]
+fn test_principal_and_yield_accounting() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    setup.usdc.mint(&setup.vault.address, &100);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.accrue(&100);
+
+    assert_eq!(setup.wallet.get_principal(), 1000);
+    assert_eq!(setup.wallet.get_yield_earned(), 100);
+
+    // Paying half of the position consumes half of the principal
+    setup.wallet.pay(&setup.provider, &550);
+
+    assert_eq!(setup.wallet.get_balance(), 550);
+    assert_eq!(setup.wallet.get_principal(), 500);
+    assert_eq!(setup.wallet.get_yield_earned(), 50);
+}
+
+#[test]
+fn test_yield_snapshots() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    let first = setup.wallet.take_yield_snapshot();
+    assert_eq!(first.yield_earned, 0);
+
+    setup.vault.accrue(&25);
+    env.ledger()
+        .with_mut(|li| li.timestamp += YIELD_SNAPSHOT_INTERVAL);
+    let second = setup.wallet.take_yield_snapshot();
+
+    assert_eq!(second.balance, 1025);
+    assert_eq!(second.principal, 1000);
+    assert_eq!(second.yield_earned, 25);
+    assert_eq!(
+        setup.wallet.get_yield_snapshots(&0, &10),
+        vec![&env, first, second]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1007)")]
+fn test_yield_snapshot_too_soon() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.take_yield_snapshot();
+    setup.wallet.take_yield_snapshot();
+}
+
+#[test]
+fn test_harvest_yield_donates_share_of_yield() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+    setup.usdc.mint(&setup.vault.address, &200);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_yield_donation(&campaign, &5000, &3600);
+    setup.vault.accrue(&200);
+
+    assert_eq!(setup.wallet.harvest_yield(), 100);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&campaign), 100);
+    assert_eq!(setup.wallet.get_principal(), 1000);
+    assert_eq!(setup.wallet.get_yield_earned(), 100);
+
+    let donation = setup.wallet.get_history(&1, &1).get(0).unwrap();
+    assert_eq!(donation.kind, TransactionKind::Donation);
+    assert_eq!(donation.counterparty, campaign);
+}
+
+#[test]
+fn test_harvest_yield_only_donates_new_yield() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+    setup.usdc.mint(&setup.vault.address, &310);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_yield_donation(&campaign, &5000, &3600);
+    setup.vault.accrue(&200);
+    assert_eq!(setup.wallet.harvest_yield(), 100);
+
+    // The yield kept at the last harvest is not donated from again
+    env.ledger().with_mut(|li| li.timestamp += 3600);
+    assert_eq!(setup.wallet.harvest_yield(), 0);
+
+    env.ledger().with_mut(|li| li.timestamp += 3600);
+    setup.vault.accrue(&110);
+    assert_eq!(setup.wallet.harvest_yield(), 55);
+
+    // Payments spend the kept yield alongside the principal
+    setup.wallet.pay(&setup.provider, &577);
+    env.ledger().with_mut(|li| li.timestamp += 3600);
+    assert_eq!(setup.wallet.harvest_yield(), 0);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&campaign), 155);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1057)")]
+fn test_set_yield_donation_zero_interval() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup
+        .wallet
+        .set_yield_donation(&Address::generate(&env), &5000, &0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1010)")]
+fn test_harvest_yield_too_soon() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let campaign = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_yield_donation(&campaign, &5000, &3600);
+    setup.wallet.harvest_yield();
+
+    env.ledger().with_mut(|li| li.timestamp += 3599);
+    setup.wallet.harvest_yield();
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1009)")]
+fn test_harvest_yield_disabled() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.harvest_yield();
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1008)")]
+fn test_set_yield_donation_invalid_percentage() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+
+    setup.wallet.set_yield_donation(&campaign, &10_001, &3600);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1011)")]
+fn test_deposit_when_protocol_paused() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.config.pause(&setup.guardian, &PauseScope::Deposits);
+    setup.wallet.deposit(&1000, &0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1011)")]
+fn test_pay_when_protocol_paused() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.config.pause(&setup.guardian, &PauseScope::Payments);
+    setup.wallet.pay(&setup.provider, &100);
+}
+
+#[test]
+fn test_pay_after_protocol_unpaused() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.config.pause(&setup.guardian, &PauseScope::All);
+    setup.config.unpause(&setup.guardian, &PauseScope::All);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.pay(&setup.provider, &100);
+    assert_eq!(setup.wallet.get_balance(), 900);
+}
+
+#[test]
+fn test_sync_config_after_address_update() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let new_registry = Address::generate(&env);
+
+    let mut addresses = setup.config.get_addresses();
+    addresses.registry = new_registry.clone();
+    setup.config.propose_addresses(&addresses);
+
+    // The wallet keeps its cached addresses while the update is pending
+    assert_eq!(setup.wallet.sync_config(), 1);
+    assert_ne!(setup.wallet.get_registry_address(), new_registry);
+
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    setup.config.execute_addresses();
+
+    assert_eq!(setup.wallet.sync_config(), 2);
+    assert_eq!(setup.wallet.get_registry_address(), new_registry);
+    assert_eq!(setup.wallet.get_defindex_contract(), setup.vault.address);
+}
+
+#[test]
+fn test_sync_config_keeps_vault_until_position_empty() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let new_registry = env.register(ProviderRegistry, (&Address::generate(&env),));
+    ProviderRegistryClient::new(&env, &new_registry).add_provider(&setup.provider);
+    let new_vault = env.register(MockVault, (&setup.usdc.address,));
+    fund_wallet(&setup, 1000);
+    setup.wallet.deposit(&1000, &0);
+
+    setup.config.propose_addresses(&ProtocolAddresses {
+        registry: new_registry.clone(),
+        usdc_token: setup.usdc.address.clone(),
+        defindex_vault: new_vault.clone(),
+    });
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    setup.config.execute_addresses();
+
+    // The shares stay in the old vault, so only the registry is adopted
+    assert_eq!(setup.wallet.sync_config(), 1);
+    assert_eq!(setup.wallet.get_registry_address(), new_registry);
+    assert_eq!(setup.wallet.get_defindex_contract(), setup.vault.address);
+    assert_eq!(setup.wallet.get_balance(), 1000);
+
+    setup.wallet.pay(&setup.provider, &1000);
+    assert_eq!(setup.wallet.get_shares(), 0);
+
+    // With the position empty the new vault is adopted
+    assert_eq!(setup.wallet.sync_config(), 2);
+    assert_eq!(setup.wallet.get_defindex_contract(), new_vault);
+
+    fund_wallet(&setup, 500);
+    setup.wallet.deposit(&500, &0);
+    assert_eq!(
+        MockVaultClient::new(&env, &new_vault).balance(&setup.wallet.address),
+        500
+    );
+}
+
+#[test]
+fn test_pay_batch() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let lab = Address::generate(&env);
+    setup.registry.add_provider(&lab);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.pay_batch(&vec![
+        &env,
+        (setup.provider.clone(), 300i128),
+        (lab.clone(), 200i128),
+    ]);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.provider), 300);
+    assert_eq!(usdc.balance(&lab), 200);
+    assert_eq!(setup.wallet.get_balance(), 500);
+    assert_eq!(setup.wallet.get_principal(), 500);
+    assert_eq!(setup.wallet.get_history_count(), 3);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1002)")]
+fn test_pay_batch_with_unapproved_provider() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let unapproved = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.pay_batch(&vec![
+        &env,
+        (setup.provider.clone(), 300i128),
+        (unapproved, 200i128),
+    ]);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1012)")]
+fn test_pay_batch_empty() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.pay_batch(&Vec::new(&env));
+}
+
+#[test]
+fn test_pay_with_memo() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    let memo = PaymentMemo {
+        invoice_ref: String::from_str(&env, "INV-2024-0042"),
+        procedure_hash: Some(BytesN::from_array(&env, &[7u8; 32])),
+        note_hash: None,
+    };
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.pay_with_memo(&setup.provider, &250, &memo);
+
+    let payment = setup.wallet.get_history(&1, &1).get(0).unwrap();
+    assert_eq!(payment.kind, TransactionKind::Payment);
+    assert_eq!(payment.amount, 250);
+    assert_eq!(setup.wallet.get_payment_memo(&1), Some(memo));
+    assert_eq!(setup.wallet.get_payment_memo(&0), None);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1013)")]
+fn test_pay_with_memo_too_long() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    let memo = PaymentMemo {
+        invoice_ref: String::from_bytes(&env, &[b'x'; MAX_INVOICE_REF_LEN as usize + 1]),
+        procedure_hash: None,
+        note_hash: None,
+    };
+
+    setup.wallet.pay_with_memo(&setup.provider, &250, &memo);
+}
+
+#[test]
+fn test_pay_charges_platform_fee() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let treasury = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.config.set_fee_config(&FeeConfig {
+        treasury: treasury.clone(),
+        bps: 100,
+        flat: 1,
+        max_fee: None,
+        exempt_categories: vec![&env],
+    });
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.pay(&setup.provider, &500);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.provider), 500);
+    assert_eq!(usdc.balance(&treasury), 6);
+    assert_eq!(setup.wallet.get_balance(), 494);
+
+    let fee = setup.wallet.get_history(&2, &1).get(0).unwrap();
+    assert_eq!(fee.kind, TransactionKind::Fee);
+    assert_eq!(fee.counterparty, treasury);
+    assert_eq!(fee.amount, 6);
+}
+
+#[test]
+fn test_pay_platform_fee_cap_and_exemption() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let treasury = Address::generate(&env);
+    let pharmacy = Address::generate(&env);
+    setup.registry.add_provider(&pharmacy);
+    setup
+        .registry
+        .set_provider_category(&pharmacy, &Symbol::new(&env, "pharmacy"));
+    fund_wallet(&setup, 10000);
+
+    setup.config.set_fee_config(&FeeConfig {
+        treasury: treasury.clone(),
+        bps: 200,
+        flat: 0,
+        max_fee: Some(50),
+        exempt_categories: vec![&env, Symbol::new(&env, "pharmacy")],
+    });
+
+    setup.wallet.deposit(&10_000, &0);
+    setup.wallet.pay_batch(&vec![
+        &env,
+        (setup.provider.clone(), 5_000i128),
+        (pharmacy.clone(), 1_000i128),
+    ]);
+
+    // 2% of 5_000 is capped at 50, the pharmacy is exempt
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&treasury), 50);
+    assert_eq!(setup.wallet.get_balance(), 3_950);
+}
+
+#[test]
+fn test_quote_payment() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let treasury = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+    setup.usdc.mint(&setup.vault.address, &200);
+
+    setup.config.set_fee_config(&FeeConfig {
+        treasury,
+        bps: 100,
+        flat: 0,
+        max_fee: None,
+        exempt_categories: vec![&env],
+    });
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.accrue(&200);
+
+    let quote = setup.wallet.quote_payment(&setup.provider, &600);
+    assert!(quote.provider_approved);
+    assert!(!quote.payments_paused);
+    assert!(!quote.approval_required);
+    assert!(!quote.frozen);
+    assert!(!quote.closed);
+    assert_eq!(quote.fee, 6);
+    assert_eq!(quote.shares_to_burn, 505);
+    assert_eq!(quote.expected_output, 606);
+    assert_eq!(quote.balance, 1200);
+    assert_eq!(quote.remaining_balance, 594);
+
+    // Quoting an unapproved provider reports it instead of failing
+    let quote = setup.wallet.quote_payment(&Address::generate(&env), &2000);
+    assert!(!quote.provider_approved);
+    assert_eq!(quote.remaining_balance, -820);
+}
+
+#[test]
+fn test_quote_payment_before_first_deposit() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    // The vault is empty, the quote still loads
+    let quote = setup.wallet.quote_payment(&setup.provider, &100);
+    assert!(quote.provider_approved);
+    assert_eq!(quote.shares_to_burn, 0);
+    assert_eq!(quote.expected_output, 0);
+    assert_eq!(quote.balance, 0);
+    assert_eq!(quote.remaining_balance, -100);
+}
+
+#[test]
+fn test_deposit_returns_minted_shares() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1500);
+
+    assert_eq!(setup.wallet.deposit(&1000, &1000), 1000);
+    setup.vault.accrue(&1000);
+
+    // Shares are now worth twice as much
+    assert_eq!(setup.wallet.deposit(&500, &250), 250);
+    assert_eq!(setup.wallet.get_shares(), 1250);
+    assert_eq!(setup.vault.balance(&setup.wallet.address), 1250);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.wallet.address), 0);
+    assert_eq!(usdc.balance(&setup.vault.address), 1500);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1014)")]
+fn test_deposit_slippage_exceeded() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1500);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.accrue(&1000);
+
+    setup.wallet.deposit(&500, &300);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1015)")]
+fn test_deposit_vault_rejected() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.vault.set_paused(&true);
+    setup.wallet.deposit(&1000, &0);
+}
+
+#[test]
+fn test_wallet_summary() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.accrue(&50);
+    setup.config.pause(&setup.guardian, &PauseScope::Payments);
+
+    let summary = setup.wallet.wallet_summary();
+    assert_eq!(summary.owner, setup.user);
+    assert_eq!(summary.defindex_vault, setup.vault.address);
+    assert_eq!(summary.vault_status, VaultStatus::Healthy);
+    assert_eq!(summary.balance, 1050);
+    assert_eq!(summary.shares, 1000);
+    assert_eq!(summary.principal, 1000);
+    assert_eq!(summary.yield_earned, 50);
+    assert!(!summary.deposits_paused);
+    assert!(summary.payments_paused);
+}
+
+#[test]
+fn test_wallet_summary_vault_unavailable() {
+    let env = Env::default();
+    let (user, registry, usdc_token, defindex_contract, _, _) = create_test_addresses(&env);
+
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
+    let client = HealthAidWalletClient::new(&env, &contract_id);
+
+    // The vault does not exist, the summary still loads
+    let summary = client.wallet_summary();
+    assert_eq!(summary.vault_status, VaultStatus::Unavailable);
+    assert_eq!(summary.balance, 0);
+    assert_eq!(summary.owner, user);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1016)")]
+fn test_get_total_supply_vault_unavailable() {
+    let env = Env::default();
+    let (user, registry, usdc_token, defindex_contract, _, _) = create_test_addresses(&env);
+
+    let contract_id = register_wallet(&env, &user, &registry, &usdc_token, &defindex_contract);
+    let client = HealthAidWalletClient::new(&env, &contract_id);
+
+    client.get_total_supply();
+}
+
+#[test]
+fn test_get_asset_allocation() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.set_idle(&200);
+    setup.vault.set_strategies(&vec![&env, false, true]);
+
+    let allocation = setup.wallet.get_asset_allocation();
+    assert_eq!(allocation.asset, setup.usdc.address);
+    assert_eq!(allocation.total_amount, 1000);
+    assert_eq!(allocation.idle_amount, 200);
+    assert_eq!(allocation.invested_amount, 800);
+    assert_eq!(allocation.strategy_allocations.len(), 2);
+    assert!(allocation.strategy_allocations.get(1).unwrap().paused);
+    assert_eq!(setup.wallet.get_vault_allocations().len(), 1);
+
+    let summary = setup.wallet.wallet_summary();
+    assert_eq!(summary.vault_status, VaultStatus::StrategiesPaused);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1017)")]
+fn test_deposit_all_strategies_paused() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.vault.set_strategies(&vec![&env, true, true]);
+    setup.wallet.deposit(&1000, &0);
+}
+
+#[test]
+fn test_pay_from_idle_while_strategy_paused() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.set_idle(&300);
+    setup.vault.set_strategies(&vec![&env, true, false]);
+
+    setup.wallet.pay(&setup.provider, &300);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.provider), 300);
+}
+
+#[test]
+fn test_pay_beyond_idle_from_active_strategies() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.set_idle(&300);
+    setup.vault.set_strategies(&vec![&env, true, false]);
+
+    // 300 idle plus the 350 held by the active strategy
+    setup.wallet.pay(&setup.provider, &650);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.provider), 650);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1018)")]
+fn test_pay_exceeds_funds_outside_paused_strategies() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.set_idle(&300);
+    setup.vault.set_strategies(&vec![&env, true, false]);
+
+    setup.wallet.pay(&setup.provider, &651);
+}
+
+#[test]
+fn test_emergency_withdraw_all() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    setup.usdc.mint(&setup.vault.address, &50);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.accrue(&50);
+
+    let redeemed = setup.wallet.emergency_withdraw_all(&setup.user);
+    assert_eq!(redeemed, 1050);
+    assert_eq!(setup.wallet.get_shares(), 0);
+    assert!(setup.wallet.is_idle_only());
+    assert_eq!(usdc.balance(&setup.wallet.address), 1050);
+    assert_eq!(setup.wallet.get_balance(), 1050);
+
+    // Payments are made from the USDC held by the wallet
+    setup.wallet.pay(&setup.provider, &300);
+    assert_eq!(usdc.balance(&setup.provider), 300);
+    assert_eq!(setup.wallet.get_balance(), 750);
+
+    // Deposits stay in the wallet
+    let funder = Address::generate(&env);
+    setup.usdc.mint(&funder, &200);
+    assert_eq!(setup.wallet.deposit_from(&funder, &200, &0), 0);
+    assert_eq!(setup.wallet.get_balance(), 950);
+
+    let summary = setup.wallet.wallet_summary();
+    assert!(summary.idle_only);
+    assert_eq!(summary.balance, 950);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1014)")]
+fn test_deposit_min_shares_while_idle_only() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1500);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.emergency_withdraw_all(&setup.user);
+
+    // No shares are minted in idle-only mode
+    setup.wallet.deposit(&500, &1);
+}
+
+#[test]
+fn test_emergency_withdraw_all_by_guardian() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    assert_eq!(setup.wallet.emergency_withdraw_all(&setup.guardian), 1000);
+    assert!(setup.wallet.is_idle_only());
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1019)")]
+fn test_emergency_withdraw_all_unauthorized() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup
+        .wallet
+        .emergency_withdraw_all(&Address::generate(&env));
+}
+
+#[test]
+fn test_enable_vault_after_emergency() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.emergency_withdraw_all(&setup.user);
+
+    let shares = setup.wallet.enable_vault(&0);
+    assert!(shares > 0);
+    assert_eq!(setup.wallet.get_shares(), shares);
+    assert!(!setup.wallet.is_idle_only());
+    assert_eq!(setup.wallet.get_balance(), 1000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1020)")]
+fn test_pay_when_frozen() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.freeze(&setup.user);
+    assert!(setup.wallet.is_frozen());
+
+    setup.wallet.pay(&setup.provider, &100);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1019)")]
+fn test_freeze_unauthorized() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.freeze(&Address::generate(&env));
+}
+
+#[test]
+fn test_unfreeze_after_delay() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.freeze(&setup.guardian);
+    setup.wallet.request_unfreeze();
+    assert_eq!(
+        setup.wallet.get_unfreeze_available_at(),
+        Some(env.ledger().timestamp() + UNFREEZE_DELAY)
+    );
+
+    env.ledger().with_mut(|li| li.timestamp += UNFREEZE_DELAY);
+    setup.wallet.unfreeze(&None);
+    assert!(!setup.wallet.is_frozen());
+
+    setup.wallet.pay(&setup.provider, &100);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1022)")]
+fn test_unfreeze_too_soon() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.freeze(&setup.user);
+    setup.wallet.request_unfreeze();
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += UNFREEZE_DELAY - 1);
+    setup.wallet.unfreeze(&None);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1021)")]
+fn test_unfreeze_not_requested() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.freeze(&setup.user);
+    setup.wallet.unfreeze(&None);
+}
+
+#[test]
+fn test_unfreeze_with_guardian_co_signature() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.freeze(&setup.user);
+    setup.wallet.unfreeze(&Some(setup.guardian.clone()));
+    assert!(!setup.wallet.is_frozen());
+    assert!(!setup.wallet.wallet_summary().frozen);
+}
+
+#[test]
+fn test_freeze_cancels_unfreeze_request() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.freeze(&setup.user);
+    setup.wallet.request_unfreeze();
+    setup.wallet.freeze(&setup.guardian);
+    assert_eq!(setup.wallet.get_unfreeze_available_at(), None);
+}
+
+#[test]
+fn test_close_returns_funds_to_owner() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    setup.usdc.mint(&setup.vault.address, &50);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.accrue(&50);
+
+    assert_eq!(setup.wallet.close(&Vec::new(&env)), 1050);
+    assert_eq!(usdc.balance(&setup.user), 1050);
+    assert_eq!(setup.wallet.get_shares(), 0);
+    assert_eq!(setup.wallet.get_principal(), 0);
+    assert!(setup.wallet.is_closed());
+
+    let record = setup.wallet.get_history(&1, &1).get(0).unwrap();
+    assert_eq!(record.kind, TransactionKind::Withdrawal);
+    assert_eq!(record.counterparty, setup.user);
+    assert_eq!(record.amount, 1050);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1023)")]
+fn test_deposit_after_close() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.close(&Vec::new(&env));
+    setup.wallet.deposit(&1000, &0);
+}
+
+#[test]
+fn test_migrate_to_new_wallet() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.accrue(&100);
+    setup.usdc.mint(&setup.vault.address, &100);
+    setup.config.set_contribution_limits(&ContributionLimits {
+        owner_limit: Some(1000),
+        third_party_limit: None,
+    });
+
+    let new_wallet = env.register(HealthAidWallet, (&setup.user, &setup.config.address));
+    setup.config.register_wallet(&new_wallet);
+    assert_eq!(setup.wallet.migrate_to(&new_wallet, &Vec::new(&env)), 1100);
+    assert!(setup.wallet.is_closed());
+    assert!(setup.wallet.wallet_summary().closed);
+
+    // The new wallet deposits the funds and keeps the principal and yield,
+    // without counting them towards the owner's yearly cap
+    let new_wallet = HealthAidWalletClient::new(&env, &new_wallet);
+    assert_eq!(usdc.balance(&new_wallet.address), 0);
+    assert_eq!(new_wallet.get_balance(), 1100);
+    assert_eq!(new_wallet.get_principal(), 1000);
+    assert_eq!(new_wallet.get_yield_earned(), 100);
+    assert_eq!(new_wallet.contributions_for_year(&1970).owner, 0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1024)")]
+fn test_migrate_to_unregistered_wallet() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    // A contract reporting the same owner and config is not enough
+    let new_wallet = env.register(HealthAidWallet, (&setup.user, &setup.config.address));
+    setup.wallet.migrate_to(&new_wallet, &Vec::new(&env));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1058)")]
+fn test_receive_migration_from_unregistered_wallet() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    let other = env.register(HealthAidWallet, (&setup.user, &setup.config.address));
+    setup.wallet.receive_migration(&other, &0, &1000);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1024)")]
+fn test_migrate_to_wallet_of_other_owner() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    let other = Address::generate(&env);
+    let new_wallet = env.register(HealthAidWallet, (&other, &setup.config.address));
+    setup.config.register_wallet(&new_wallet);
+    setup.wallet.migrate_to(&new_wallet, &Vec::new(&env));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1024)")]
+fn test_migrate_to_non_wallet() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup
+        .wallet
+        .migrate_to(&setup.vault.address, &Vec::new(&env));
+}
+
+fn beneficiaries(env: &Env, shares: &[(Address, u32)]) -> Vec<Beneficiary> {
+    let mut beneficiaries = Vec::new(env);
+    for (address, share_bps) in shares {
+        beneficiaries.push_back(Beneficiary {
+            address: address.clone(),
+            share_bps: *share_bps,
+        });
+    }
+    beneficiaries
+}
+
+#[test]
+fn test_claim_inheritance_after_inactivity() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_beneficiaries(
+        &beneficiaries(&env, &[(alice.clone(), 6000), (bob.clone(), 4000)]),
+        &MIN_INACTIVITY_PERIOD,
+    );
+    assert_eq!(
+        setup.wallet.get_release_available_at(),
+        Some(env.ledger().timestamp() + MIN_INACTIVITY_PERIOD)
+    );
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += MIN_INACTIVITY_PERIOD);
+    assert_eq!(setup.wallet.claim_inheritance(&alice), 600);
+    assert_eq!(setup.wallet.claim_inheritance(&bob), 400);
+    assert_eq!(usdc.balance(&alice), 600);
+    assert_eq!(usdc.balance(&bob), 400);
+    assert_eq!(setup.wallet.get_principal(), 0);
+}
+
+#[test]
+fn test_claim_inheritance_leaves_held_funds() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    let alice = Address::generate(&env);
+    let expiry = env.ledger().timestamp() + 2 * MIN_INACTIVITY_PERIOD;
+
+    setup.wallet.deposit(&1000, &0);
+    let hold_id = setup.wallet.authorize_hold(&setup.provider, &400, &expiry);
+    setup.wallet.set_beneficiaries(
+        &beneficiaries(&env, &[(alice.clone(), 10_000)]),
+        &MIN_INACTIVITY_PERIOD,
+    );
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += MIN_INACTIVITY_PERIOD);
+    assert_eq!(setup.wallet.claim_inheritance(&alice), 600);
+    assert_eq!(usdc.balance(&alice), 600);
+
+    // The provider can still capture the reserved funds
+    setup.wallet.capture(&hold_id, &400);
+    assert_eq!(usdc.balance(&setup.provider), 400);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1026)")]
+fn test_claim_inheritance_before_inactivity_period() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let alice = Address::generate(&env);
+
+    setup.wallet.set_beneficiaries(
+        &beneficiaries(&env, &[(alice.clone(), 10_000)]),
+        &MIN_INACTIVITY_PERIOD,
+    );
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += MIN_INACTIVITY_PERIOD - 1);
+    setup.wallet.claim_inheritance(&alice);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1026)")]
+fn test_owner_action_cancels_inheritance_release() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_beneficiaries(
+        &beneficiaries(&env, &[(alice.clone(), 5000), (bob.clone(), 5000)]),
+        &MIN_INACTIVITY_PERIOD,
+    );
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += MIN_INACTIVITY_PERIOD);
+    setup.wallet.claim_inheritance(&alice);
+
+    // The owner shows up again, the remaining claims are cancelled
+    setup.wallet.pay(&setup.provider, &100);
+    setup.wallet.claim_inheritance(&bob);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1028)")]
+fn test_claim_inheritance_twice() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_beneficiaries(
+        &beneficiaries(&env, &[(alice.clone(), 5000), (bob.clone(), 5000)]),
+        &MIN_INACTIVITY_PERIOD,
+    );
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += MIN_INACTIVITY_PERIOD);
+    setup.wallet.claim_inheritance(&alice);
+    setup.wallet.claim_inheritance(&alice);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1027)")]
+fn test_claim_inheritance_not_beneficiary() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let alice = Address::generate(&env);
+
+    setup.wallet.set_beneficiaries(
+        &beneficiaries(&env, &[(alice, 10_000)]),
+        &MIN_INACTIVITY_PERIOD,
+    );
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += MIN_INACTIVITY_PERIOD);
+    setup.wallet.claim_inheritance(&Address::generate(&env));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1025)")]
+fn test_set_beneficiaries_shares_must_sum_to_total() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.set_beneficiaries(
+        &beneficiaries(&env, &[(Address::generate(&env), 9000)]),
+        &MIN_INACTIVITY_PERIOD,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1001)")]
+fn test_deposit_escrowed_funds() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.pay_escrow(&setup.provider, &400);
+
+    // The 400 held by the wallet is owed to the provider
+    setup.wallet.deposit(&400, &0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1001)")]
+fn test_deposit_more_than_held() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1001, &0);
+}
+
+#[test]
+fn test_pay_escrow_and_confirm_service() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+
+    setup.wallet.deposit(&1000, &0);
+    let escrow_id = setup.wallet.pay_escrow(&setup.provider, &400);
+
+    // The payment is held by the wallet
+    assert_eq!(usdc.balance(&setup.provider), 0);
+    assert_eq!(usdc.balance(&setup.wallet.address), 400);
+    assert_eq!(setup.wallet.get_escrowed_total(), 400);
+    assert_eq!(setup.wallet.get_balance(), 600);
+    let escrow = setup.wallet.get_escrow(&escrow_id).unwrap();
+    assert_eq!(escrow.status, EscrowStatus::Pending);
+    assert_eq!(escrow.release_after, escrow.created_at + DISPUTE_WINDOW);
+
+    setup.wallet.confirm_service(&escrow_id);
+    assert_eq!(usdc.balance(&setup.provider), 400);
+    assert_eq!(setup.wallet.get_escrowed_total(), 0);
+    assert_eq!(
+        setup.wallet.get_escrow(&escrow_id).unwrap().status,
+        EscrowStatus::Released
+    );
+
+    let record = setup.wallet.get_history(&1, &1).get(0).unwrap();
+    assert_eq!(record.kind, TransactionKind::Payment);
+    assert_eq!(record.amount, 400);
+}
+
+#[test]
+fn test_release_escrow_after_dispute_window() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+
+    setup.wallet.deposit(&1000, &0);
+    let escrow_id = setup.wallet.pay_escrow(&setup.provider, &400);
+
+    env.ledger().with_mut(|li| li.timestamp += DISPUTE_WINDOW);
+    setup.wallet.release_escrow(&escrow_id);
+    assert_eq!(usdc.balance(&setup.provider), 400);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1031)")]
+fn test_release_escrow_during_dispute_window() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    let escrow_id = setup.wallet.pay_escrow(&setup.provider, &400);
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += DISPUTE_WINDOW - 1);
+    setup.wallet.release_escrow(&escrow_id);
+}
+
+#[test]
+fn test_dispute_refunded_by_arbiter() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    setup.registry.set_arbiter(&Address::generate(&env));
+
+    setup.wallet.deposit(&1000, &0);
+    let escrow_id = setup.wallet.pay_escrow(&setup.provider, &400);
+    setup.wallet.dispute_payment(&escrow_id);
+
+    setup.wallet.resolve_dispute(&escrow_id, &false);
+    assert_eq!(usdc.balance(&setup.provider), 0);
+    assert_eq!(setup.wallet.get_escrowed_total(), 0);
+    assert_eq!(setup.wallet.get_principal(), 1000);
+    assert_eq!(
+        setup.wallet.get_escrow(&escrow_id).unwrap().status,
+        EscrowStatus::Refunded
+    );
+
+    let record = setup.wallet.get_history(&1, &1).get(0).unwrap();
+    assert_eq!(record.kind, TransactionKind::Refund);
+    assert_eq!(record.amount, 400);
+}
+
+#[test]
+fn test_refund_keeps_yield_accrued_before_escrow() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    setup.usdc.mint(&setup.vault.address, &250);
+    setup.registry.set_arbiter(&Address::generate(&env));
+
+    setup.wallet.deposit(&1000, &0);
+    setup.vault.accrue(&250);
+    let escrow_id = setup.wallet.pay_escrow(&setup.provider, &500);
+    assert_eq!(setup.wallet.get_principal(), 600);
+    assert_eq!(setup.wallet.get_escrow(&escrow_id).unwrap().cost_basis, 400);
+
+    setup.wallet.dispute_payment(&escrow_id);
+    setup.wallet.resolve_dispute(&escrow_id, &false);
+    assert_eq!(setup.wallet.get_principal(), 1000);
+    assert_eq!(setup.wallet.get_balance(), 1250);
+    assert_eq!(setup.wallet.get_yield_earned(), 250);
+}
+
+#[test]
+fn test_dispute_released_by_arbiter() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    setup.registry.set_arbiter(&Address::generate(&env));
+
+    setup.wallet.deposit(&1000, &0);
+    let escrow_id = setup.wallet.pay_escrow(&setup.provider, &400);
+    setup.wallet.dispute_payment(&escrow_id);
+
+    setup.wallet.resolve_dispute(&escrow_id, &true);
+    assert_eq!(usdc.balance(&setup.provider), 400);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1032)")]
+fn test_dispute_after_window() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    let escrow_id = setup.wallet.pay_escrow(&setup.provider, &400);
+
+    env.ledger().with_mut(|li| li.timestamp += DISPUTE_WINDOW);
+    setup.wallet.dispute_payment(&escrow_id);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1019)")]
+fn test_resolve_dispute_without_arbiter() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    let escrow_id = setup.wallet.pay_escrow(&setup.provider, &400);
+    setup.wallet.dispute_payment(&escrow_id);
+
+    setup.wallet.resolve_dispute(&escrow_id, &true);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1033)")]
+fn test_close_with_pending_escrow() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.pay_escrow(&setup.provider, &400);
+    setup.wallet.close(&Vec::new(&env));
+}
+
+#[test]
+fn test_hold_reserves_balance_and_capture() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    let expiry = env.ledger().timestamp() + 3 * 24 * 60 * 60;
+
+    setup.wallet.deposit(&1000, &0);
+    let hold_id = setup.wallet.authorize_hold(&setup.provider, &700, &expiry);
+    assert_eq!(setup.wallet.get_reserved_balance(), 700);
+    assert_eq!(setup.wallet.get_spendable_balance(), 300);
+    assert_eq!(setup.wallet.wallet_summary().reserved, 700);
+
+    setup.wallet.capture(&hold_id, &550);
+    assert_eq!(usdc.balance(&setup.provider), 550);
+    assert_eq!(setup.wallet.get_reserved_balance(), 0);
+    assert_eq!(setup.wallet.get_spendable_balance(), 450);
+
+    let hold = setup.wallet.get_hold(&hold_id).unwrap();
+    assert_eq!(hold.status, HoldStatus::Captured);
+    assert_eq!(hold.captured, 550);
+}
+
+#[test]
+fn test_hold_reserves_fee_for_full_capture() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let treasury = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    let expiry = env.ledger().timestamp() + 24 * 60 * 60;
+
+    setup.config.set_fee_config(&FeeConfig {
+        treasury: treasury.clone(),
+        bps: 100,
+        flat: 1,
+        max_fee: None,
+        exempt_categories: vec![&env],
+    });
+
+    setup.wallet.deposit(&1000, &0);
+    let hold_id = setup.wallet.authorize_hold(&setup.provider, &700, &expiry);
+    assert_eq!(setup.wallet.get_hold(&hold_id).unwrap().fee, 8);
+    assert_eq!(setup.wallet.get_reserved_balance(), 708);
+
+    // Spend everything that is not reserved
+    setup.wallet.pay(&setup.provider, &289);
+    assert_eq!(setup.wallet.get_spendable_balance(), 0);
+
+    setup.wallet.capture(&hold_id, &700);
+    assert_eq!(usdc.balance(&setup.provider), 989);
+    assert_eq!(usdc.balance(&treasury), 11);
+    assert_eq!(setup.wallet.get_balance(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1001)")]
+fn test_authorize_hold_without_balance_for_fee() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let expiry = env.ledger().timestamp() + 24 * 60 * 60;
+
+    setup.config.set_fee_config(&FeeConfig {
+        treasury: Address::generate(&env),
+        bps: 100,
+        flat: 0,
+        max_fee: None,
+        exempt_categories: vec![&env],
+    });
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.authorize_hold(&setup.provider, &992, &expiry);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1001)")]
+fn test_pay_cannot_spend_reserved_balance() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let expiry = env.ledger().timestamp() + 24 * 60 * 60;
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.authorize_hold(&setup.provider, &700, &expiry);
+    setup.wallet.pay(&setup.provider, &400);
+}
+
+#[test]
+fn test_hold_released_on_expiry() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let expiry = env.ledger().timestamp() + 24 * 60 * 60;
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.authorize_hold(&setup.provider, &700, &expiry);
+
+    env.ledger().with_mut(|li| li.timestamp = expiry);
+    assert_eq!(setup.wallet.get_reserved_balance(), 0);
+    setup.wallet.pay(&setup.provider, &400);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1036)")]
+fn test_capture_after_expiry() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let expiry = env.ledger().timestamp() + 24 * 60 * 60;
+
+    setup.wallet.deposit(&1000, &0);
+    let hold_id = setup.wallet.authorize_hold(&setup.provider, &700, &expiry);
+
+    env.ledger().with_mut(|li| li.timestamp = expiry);
+    setup.wallet.capture(&hold_id, &500);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1037)")]
+fn test_capture_exceeds_hold() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let expiry = env.ledger().timestamp() + 24 * 60 * 60;
+
+    setup.wallet.deposit(&1000, &0);
+    let hold_id = setup.wallet.authorize_hold(&setup.provider, &700, &expiry);
+    setup.wallet.capture(&hold_id, &701);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1035)")]
+fn test_capture_released_hold() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let expiry = env.ledger().timestamp() + 24 * 60 * 60;
+
+    setup.wallet.deposit(&1000, &0);
+    let hold_id = setup.wallet.authorize_hold(&setup.provider, &700, &expiry);
+    setup.wallet.release_hold(&setup.user, &hold_id);
+    assert_eq!(setup.wallet.get_reserved_balance(), 0);
+
+    setup.wallet.capture(&hold_id, &500);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1001)")]
+fn test_authorize_hold_exceeds_spendable_balance() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let expiry = env.ledger().timestamp() + 24 * 60 * 60;
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.authorize_hold(&setup.provider, &600, &expiry);
+    setup.wallet.authorize_hold(&setup.provider, &500, &expiry);
+}
+
+/// Register a signing key for `setup.provider` and build a signed request
+fn signed_request(
+    env: &Env,
+    setup: &WalletSetup,
+    amount: i128,
+    nonce: u64,
+) -> (PaymentRequest, BytesN<64>) {
+    let signing_key = SigningKey::from_bytes(&[7; 32]);
+    setup.registry.set_provider_key(
+        &setup.provider,
+        &BytesN::from_array(env, &signing_key.verifying_key().to_bytes()),
+    );
+
+    let request = PaymentRequest {
+        provider: setup.provider.clone(),
+        wallet: setup.wallet.address.clone(),
+        amount,
+        currency: setup.usdc.address.clone(),
+        invoice_ref: String::from_str(env, "INV-2024-0042"),
+        nonce,
+        expiry: env.ledger().timestamp() + 15 * 60,
+    };
+    let signature = sign_request(env, &request);
+    (request, signature)
+}
+
+/// Sign a payment request with the provider key registered by `signed_request`
+fn sign_request(env: &Env, request: &PaymentRequest) -> BytesN<64> {
+    let signing_key = SigningKey::from_bytes(&[7; 32]);
+    let message: std::vec::Vec<u8> = request.clone().to_xdr(env).iter().collect();
+    BytesN::from_array(env, &signing_key.sign(&message).to_bytes())
+}
+
+#[test]
+fn test_pay_request() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+
+    setup.wallet.deposit(&1000, &0);
+    let (request, signature) = signed_request(&env, &setup, 250, 1);
+    setup.wallet.pay_request(&request, &signature);
+
+    assert_eq!(usdc.balance(&setup.provider), 250);
+    assert!(setup.wallet.is_request_paid(&setup.provider, &1));
+    let memo = setup.wallet.get_payment_memo(&1).unwrap();
+    assert_eq!(memo.invoice_ref, request.invoice_ref);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1041)")]
+fn test_pay_request_replay() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    let (request, signature) = signed_request(&env, &setup, 250, 1);
+    setup.wallet.pay_request(&request, &signature);
+    setup.wallet.pay_request(&request, &signature);
+}
+
+#[test]
+#[should_panic(expected = "Error(Crypto, InvalidInput)")]
+fn test_pay_request_tampered_amount() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    let (mut request, signature) = signed_request(&env, &setup, 250, 1);
+    request.amount = 900;
+    setup.wallet.pay_request(&request, &signature);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1040)")]
+fn test_pay_request_expired() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    let (request, signature) = signed_request(&env, &setup, 250, 1);
+    env.ledger().with_mut(|li| li.timestamp = request.expiry);
+    setup.wallet.pay_request(&request, &signature);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1042)")]
+fn test_pay_request_unsupported_currency() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    let (mut request, signature) = signed_request(&env, &setup, 250, 1);
+    request.currency = Address::generate(&env);
+    setup.wallet.pay_request(&request, &signature);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1059)")]
+fn test_pay_request_signed_for_other_wallet() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let other_wallet = env.register(HealthAidWallet, (&setup.user, &setup.config.address));
+
+    setup.wallet.deposit(&1000, &0);
+    let (mut request, _) = signed_request(&env, &setup, 250, 1);
+    request.wallet = other_wallet;
+    let signature = sign_request(&env, &request);
+    setup.wallet.pay_request(&request, &signature);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1039)")]
+fn test_pay_request_without_provider_key() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    let request = PaymentRequest {
+        provider: setup.provider.clone(),
+        wallet: setup.wallet.address.clone(),
+        amount: 250,
+        currency: setup.usdc.address.clone(),
+        invoice_ref: String::from_str(&env, "INV-2024-0042"),
+        nonce: 1,
+        expiry: env.ledger().timestamp() + 15 * 60,
+    };
+    setup
+        .wallet
+        .pay_request(&request, &BytesN::from_array(&env, &[0; 64]));
+}
+
+fn approval_policy(signers: Vec<Address>, required_approvals: u32) -> ApprovalPolicy {
+    ApprovalPolicy {
+        threshold_amount: 500,
+        signers,
+        required_approvals,
+        proposal_ttl: 3 * 24 * 60 * 60,
+    }
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1043)")]
+fn test_pay_above_threshold_requires_approval() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let family = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family], 1)),
+        &Vec::new(&env),
+    );
+
+    setup.wallet.pay(&setup.provider, &500);
+    setup.wallet.pay(&setup.provider, &501);
+}
+
+#[test]
+fn test_quote_payment_reports_approval_and_freeze() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let family = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family], 1)),
+        &Vec::new(&env),
+    );
+
+    assert!(
+        !setup
+            .wallet
+            .quote_payment(&setup.provider, &500)
+            .approval_required
+    );
+    assert!(
+        setup
+            .wallet
+            .quote_payment(&setup.provider, &501)
+            .approval_required
+    );
+
+    setup.wallet.freeze(&setup.user);
+    assert!(setup.wallet.quote_payment(&setup.provider, &500).frozen);
+}
+
+#[test]
+fn test_propose_approve_execute_payment() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    let family = Address::generate(&env);
+    let case_manager = Address::generate(&env);
+    let other = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(
+            vec![&env, family.clone(), case_manager.clone(), other],
+            2,
+        )),
+        &Vec::new(&env),
+    );
+
+    let proposal_id = setup.wallet.propose_payment(&setup.provider, &800);
+    setup.wallet.approve_payment(&proposal_id, &family);
+    setup.wallet.approve_payment(&proposal_id, &case_manager);
+    setup.wallet.execute_payment(&proposal_id);
+
+    assert_eq!(usdc.balance(&setup.provider), 800);
+    assert_eq!(
+        setup.wallet.get_proposal(&proposal_id).unwrap().status,
+        ProposalStatus::Executed
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1050)")]
+fn test_execute_payment_insufficient_approvals() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let family = Address::generate(&env);
+    let case_manager = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family.clone(), case_manager], 2)),
+        &Vec::new(&env),
+    );
+
+    let proposal_id = setup.wallet.propose_payment(&setup.provider, &800);
+    setup.wallet.approve_payment(&proposal_id, &family);
+    setup.wallet.execute_payment(&proposal_id);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1047)")]
+fn test_execute_expired_proposal() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let family = Address::generate(&env);
+    let policy = approval_policy(vec![&env, family.clone()], 1);
+
+    setup.wallet.deposit(&1000, &0);
+    setup
+        .wallet
+        .set_approval_policy(&Some(policy.clone()), &Vec::new(&env));
+
+    let proposal_id = setup.wallet.propose_payment(&setup.provider, &800);
+    setup.wallet.approve_payment(&proposal_id, &family);
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += policy.proposal_ttl);
+    setup.wallet.execute_payment(&proposal_id);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1048)")]
+fn test_approve_payment_not_signer() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family], 1)),
+        &Vec::new(&env),
+    );
+
+    let proposal_id = setup.wallet.propose_payment(&setup.provider, &800);
+    setup
+        .wallet
+        .approve_payment(&proposal_id, &Address::generate(&env));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1049)")]
+fn test_approve_payment_twice() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family.clone()], 1)),
+        &Vec::new(&env),
+    );
+
+    let proposal_id = setup.wallet.propose_payment(&setup.provider, &800);
+    setup.wallet.approve_payment(&proposal_id, &family);
+    setup.wallet.approve_payment(&proposal_id, &family);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1050)")]
+fn test_remove_approval_policy_requires_co_signers() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family], 1)),
+        &Vec::new(&env),
+    );
+    setup.wallet.set_approval_policy(&None, &Vec::new(&env));
+}
+
+#[test]
+fn test_remove_approval_policy_with_co_signers() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family.clone()], 1)),
+        &Vec::new(&env),
+    );
+    setup.wallet.set_approval_policy(&None, &vec![&env, family]);
+    assert_eq!(setup.wallet.get_approval_policy(), None);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1044)")]
+fn test_set_approval_policy_duplicate_signers() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family.clone(), family], 2)),
+        &Vec::new(&env),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1044)")]
+fn test_set_approval_policy_zero_ttl() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let mut policy = approval_policy(vec![&env, Address::generate(&env)], 1);
+    policy.proposal_ttl = 0;
+
+    setup
+        .wallet
+        .set_approval_policy(&Some(policy), &Vec::new(&env));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1048)")]
+fn test_remove_approval_policy_duplicate_co_signers() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+    let case_manager = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family.clone(), case_manager], 2)),
+        &Vec::new(&env),
+    );
+    setup
+        .wallet
+        .set_approval_policy(&None, &vec![&env, family.clone(), family]);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_remove_approval_policy_without_co_signer_auth() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family.clone()], 1)),
+        &Vec::new(&env),
+    );
+
+    // Only the owner signs; naming the co-signer is not enough
+    let co_signers = vec![&env, family];
+    env.mock_auths(&[MockAuth {
+        address: &setup.user,
+        invoke: &MockAuthInvoke {
+            contract: &setup.wallet.address,
+            fn_name: "set_approval_policy",
+            args: (None::<ApprovalPolicy>, co_signers.clone()).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    setup.wallet.set_approval_policy(&None, &co_signers);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_approve_payment_without_signer_auth() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family.clone()], 1)),
+        &Vec::new(&env),
+    );
+    let proposal_id = setup.wallet.propose_payment(&setup.provider, &800);
+
+    env.mock_auths(&[]);
+    setup.wallet.approve_payment(&proposal_id, &family);
+}
+
+#[test]
+fn test_cancel_proposal() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family], 1)),
+        &Vec::new(&env),
+    );
+    let proposal_id = setup.wallet.propose_payment(&setup.provider, &800);
+    let head = setup.wallet.get_audit_head();
+
+    setup.wallet.cancel_proposal(&proposal_id);
+    let head = replay_audit_events(&env, &setup.wallet.address, head);
+    assert_eq!(setup.wallet.get_audit_head(), head);
+    assert_eq!(
+        setup.wallet.get_proposal(&proposal_id).unwrap().status,
+        ProposalStatus::Cancelled
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1050)")]
+fn test_close_with_policy_requires_co_signers() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let family = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family], 1)),
+        &Vec::new(&env),
+    );
+    setup.wallet.close(&Vec::new(&env));
+}
+
+#[test]
+fn test_close_with_policy_and_co_signers() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let family = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family.clone()], 1)),
+        &Vec::new(&env),
+    );
+    assert_eq!(setup.wallet.close(&vec![&env, family]), 1000);
+    assert!(setup.wallet.is_closed());
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1050)")]
+fn test_migrate_with_policy_requires_co_signers() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let family = Address::generate(&env);
+
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, family], 1)),
+        &Vec::new(&env),
+    );
+    let new_wallet = env.register(HealthAidWallet, (&setup.user, &setup.config.address));
+    setup.config.register_wallet(&new_wallet);
+    setup.wallet.migrate_to(&new_wallet, &Vec::new(&env));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1051)")]
+fn test_pay_blocked_provider() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.block_provider(&setup.provider);
+    assert!(setup.wallet.is_blocked(&setup.provider));
+    assert!(
+        !setup
+            .wallet
+            .quote_payment(&setup.provider, &100)
+            .allowed_by_wallet
+    );
+
+    setup.wallet.pay(&setup.provider, &100);
+}
+
+#[test]
+fn test_unblock_provider() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.block_provider(&setup.provider);
+    setup.wallet.unblock_provider(&setup.provider);
+
+    setup.wallet.pay(&setup.provider, &100);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1052)")]
+fn test_allowlist_mode_rejects_non_favourite() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let pharmacy = Address::generate(&env);
+    setup.registry.add_provider(&pharmacy);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_allowlist_mode(&true);
+    setup.wallet.add_favourite(&setup.provider);
+
+    setup.wallet.pay(&setup.provider, &100);
+    setup.wallet.pay(&pharmacy, &100);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1002)")]
+fn test_favourite_must_be_registered_provider() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+    let stranger = Address::generate(&env);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_allowlist_mode(&true);
+    setup.wallet.add_favourite(&stranger);
+
+    setup.wallet.pay(&stranger, &100);
+}
+
+#[test]
+fn test_wallet_config_change_after_timelock() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let new_registry = Address::generate(&env);
+
+    let mut addresses = setup.config.get_addresses();
+    addresses.registry = new_registry.clone();
+    setup.wallet.propose_config(&addresses);
+
+    let pending = setup.wallet.pending_config().unwrap();
+    assert_eq!(pending.addresses, addresses);
+    assert_eq!(
+        pending.executable_at,
+        env.ledger().timestamp() + UPDATE_TIMELOCK
+    );
+    assert_ne!(setup.wallet.get_registry_address(), new_registry);
+
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    setup.wallet.execute_config();
+    assert_eq!(setup.wallet.get_registry_address(), new_registry);
+    assert_eq!(setup.wallet.pending_config(), None);
+    assert!(setup.wallet.has_address_override());
+
+    // Protocol config updates no longer override the wallet's addresses
+    let mut protocol_addresses = setup.config.get_addresses();
+    protocol_addresses.registry = Address::generate(&env);
+    setup.config.propose_addresses(&protocol_addresses);
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    setup.config.execute_addresses();
+    setup.wallet.sync_config();
+    assert_eq!(setup.wallet.get_registry_address(), new_registry);
+
+    setup.wallet.follow_protocol_config();
+    assert!(!setup.wallet.has_address_override());
+    assert_eq!(
+        setup.wallet.get_registry_address(),
+        protocol_addresses.registry
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1054)")]
+fn test_wallet_config_change_before_timelock() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    let mut addresses = setup.config.get_addresses();
+    addresses.registry = Address::generate(&env);
+    setup.wallet.propose_config(&addresses);
+
+    env.ledger()
+        .with_mut(|li| li.timestamp += UPDATE_TIMELOCK - 1);
+    setup.wallet.execute_config();
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1053)")]
+fn test_cancel_wallet_config_change() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    let mut addresses = setup.config.get_addresses();
+    addresses.registry = Address::generate(&env);
+    setup.wallet.propose_config(&addresses);
+    setup.wallet.cancel_config();
+    assert_eq!(setup.wallet.pending_config(), None);
+
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    setup.wallet.execute_config();
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1055)")]
+fn test_wallet_config_vault_change_with_open_position() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+
+    let mut addresses = setup.config.get_addresses();
+    addresses.defindex_vault = Address::generate(&env);
+    setup.wallet.propose_config(&addresses);
+
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    setup.wallet.execute_config();
+}
+
+#[test]
+fn test_contributions_tracked_per_year_and_bucket() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 400);
+    let funder = Address::generate(&env);
+    setup.usdc.mint(&funder, &1000);
+    setup.usdc.mint(&setup.user, &1000);
+
+    // 2023-12-31T23:59:59Z
+    env.ledger().with_mut(|li| li.timestamp = 1_704_067_199);
+    setup.wallet.deposit(&100, &0);
+
+    // 2024-01-01T00:00:00Z
+    env.ledger().with_mut(|li| li.timestamp = 1_704_067_200);
+    setup.wallet.deposit(&300, &0);
+    setup.wallet.deposit_from(&funder, &200, &0);
+
+    // 2024-12-31T23:59:59Z
+    env.ledger().with_mut(|li| li.timestamp = 1_735_689_599);
+    setup.wallet.deposit_from(&setup.user, &50, &0);
+
+    assert_eq!(
+        setup.wallet.contributions_for_year(&2023),
+        YearContributions {
+            owner: 100,
+            third_party: 0
+        }
+    );
+    assert_eq!(
+        setup.wallet.contributions_for_year(&2024),
+        YearContributions {
+            owner: 350,
+            third_party: 200
+        }
+    );
+    assert_eq!(
+        setup.wallet.contributions_for_year(&2025),
+        YearContributions::default()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1056)")]
+fn test_owner_contribution_limit() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1001);
+    let funder = Address::generate(&env);
+    setup.usdc.mint(&funder, &5000);
+    setup.config.set_contribution_limits(&ContributionLimits {
+        owner_limit: Some(1000),
+        third_party_limit: None,
+    });
+
+    setup.wallet.deposit(&800, &0);
+    // Third-party deposits have their own bucket
+    setup.wallet.deposit_from(&funder, &5000, &0);
+    setup.wallet.deposit(&201, &0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1056)")]
+fn test_third_party_contribution_limit() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let funder = Address::generate(&env);
+    let employer = Address::generate(&env);
+    setup.usdc.mint(&funder, &600);
+    setup.usdc.mint(&employer, &600);
+    setup.config.set_contribution_limits(&ContributionLimits {
+        owner_limit: None,
+        third_party_limit: Some(1000),
+    });
+
+    setup.wallet.deposit_from(&funder, &600, &0);
+    setup.wallet.deposit_from(&employer, &600, &0);
+}
+
+#[test]
+fn test_contribution_limit_resets_each_year() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 2000);
+    setup.config.set_contribution_limits(&ContributionLimits {
+        owner_limit: Some(1000),
+        third_party_limit: None,
+    });
+
+    env.ledger().with_mut(|li| li.timestamp = 1_735_689_599);
+    setup.wallet.deposit(&1000, &0);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_735_689_600);
+    setup.wallet.deposit(&1000, &0);
+    assert_eq!(setup.wallet.contributions_for_year(&2025).owner, 1000);
+}
+
+#[test]
+fn test_pay_rounds_up_to_campaign() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_round_up(&campaign, &10, &100);
+    assert_eq!(setup.wallet.quote_payment(&setup.provider, &93).round_up, 7);
+
+    setup.wallet.pay(&setup.provider, &93);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.provider), 93);
+    assert_eq!(usdc.balance(&campaign), 7);
+    assert_eq!(setup.wallet.get_balance(), 900);
+    assert_eq!(setup.wallet.get_total_donated(), 7);
+    assert_eq!(setup.wallet.get_round_up_donated(&1970, &1), 7);
+
+    let donation = setup.wallet.get_history(&2, &1).get(0).unwrap();
+    assert_eq!(donation.kind, TransactionKind::Donation);
+    assert_eq!(donation.counterparty, campaign);
+
+    // Exact multiples of the increment are not rounded up
+    setup.wallet.pay(&setup.provider, &100);
+    assert_eq!(setup.wallet.get_total_donated(), 7);
+}
+
+#[test]
+fn test_round_up_monthly_cap() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_round_up(&campaign, &10, &10);
+
+    // 2024-01-15T00:00:00Z
+    env.ledger().with_mut(|li| li.timestamp = 1_705_276_800);
+    setup.wallet.pay(&setup.provider, &93);
+    setup.wallet.pay(&setup.provider, &95);
+    setup.wallet.pay(&setup.provider, &91);
+    assert_eq!(setup.wallet.get_round_up_donated(&2024, &1), 10);
+
+    // 2024-02-01T00:00:00Z
+    env.ledger().with_mut(|li| li.timestamp = 1_706_745_600);
+    setup.wallet.pay(&setup.provider, &91);
+    assert_eq!(setup.wallet.get_round_up_donated(&2024, &2), 9);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&campaign), 19);
+    assert_eq!(setup.wallet.get_total_donated(), 19);
+}
+
+#[test]
+fn test_round_up_skipped_when_balance_short() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 95);
+
+    setup.wallet.deposit(&95, &0);
+    setup.wallet.set_round_up(&campaign, &10, &100);
+    setup.wallet.pay(&setup.provider, &93);
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.provider), 93);
+    assert_eq!(usdc.balance(&campaign), 0);
+    assert_eq!(setup.wallet.get_balance(), 2);
+    assert_eq!(setup.wallet.get_total_donated(), 0);
+}
+
+#[test]
+fn test_disable_round_up() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_round_up(&campaign, &10, &100);
+    setup.wallet.disable_round_up();
+    assert_eq!(setup.wallet.get_round_up(), None);
+
+    setup.wallet.pay(&setup.provider, &93);
+    assert_eq!(setup.wallet.get_balance(), 907);
+    assert_eq!(setup.wallet.get_total_donated(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1003)")]
+fn test_set_round_up_invalid_increment() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup
+        .wallet
+        .set_round_up(&Address::generate(&env), &0, &100);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1003)")]
+fn test_set_round_up_increment_too_large() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    setup.wallet.set_round_up(
+        &Address::generate(&env),
+        &(MAX_ROUND_UP_INCREMENT + 1),
+        &i128::MAX,
+    );
+}
+
+#[test]
+fn test_round_up_capped_at_payment() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_round_up(&campaign, &100, &1000);
+    assert_eq!(setup.wallet.quote_payment(&setup.provider, &1).round_up, 1);
+
+    setup.wallet.pay(&setup.provider, &1);
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&campaign), 1);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1043)")]
+fn test_round_up_counts_towards_approval_threshold() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_approval_policy(
+        &Some(approval_policy(vec![&env, Address::generate(&env)], 1)),
+        &Vec::new(&env),
+    );
+    setup
+        .wallet
+        .set_round_up(&Address::generate(&env), &30, &100);
+    assert!(
+        setup
+            .wallet
+            .quote_payment(&setup.provider, &495)
+            .approval_required
+    );
+
+    setup.wallet.pay(&setup.provider, &495);
+}
+
+#[test]
+fn test_pay_with_memo_rounds_up() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.wallet.set_round_up(&campaign, &10, &100);
+    setup.wallet.pay_with_memo(
+        &setup.provider,
+        &93,
+        &PaymentMemo {
+            invoice_ref: String::from_str(&env, "INV-1"),
+            procedure_hash: None,
+            note_hash: None,
+        },
+    );
+
+    let usdc = TokenClient::new(&env, &setup.usdc.address);
+    assert_eq!(usdc.balance(&setup.provider), 93);
+    assert_eq!(usdc.balance(&campaign), 7);
+}
+
+/// Extend an audit chain with the wallet's events from the last invocation,
+/// recomputing each link the way the wallet does on-chain
+fn replay_audit_events(env: &Env, wallet: &Address, mut head: BytesN<32>) -> BytesN<32> {
+    for (contract, topics, data) in env.events().all().iter() {
+        if contract != *wallet {
+            continue;
+        }
+        let mut entry = Bytes::from_array(env, &head.to_array());
+        entry.append(&topics.to_xdr(env));
+        entry.append(&data.to_xdr(env));
+        head = env.crypto().sha256(&entry).to_bytes();
+    }
+    head
+}
+
+#[test]
+fn test_audit_chain_starts_from_zero() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+
+    // The chain of a new wallet starts with the constructor's events
+    let wallet_id = env.register(HealthAidWallet, (&setup.user, &setup.config.address));
+    let head = replay_audit_events(&env, &wallet_id, BytesN::from_array(&env, &[0; 32]));
+    assert_eq!(
+        HealthAidWalletClient::new(&env, &wallet_id).get_audit_head(),
+        head
+    );
+}
+
+#[test]
+fn test_audit_chain_matches_events() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let wallet = setup.wallet.address.clone();
+    let campaign = Address::generate(&env);
+    fund_wallet(&setup, 1000);
+
+    let mut head = setup.wallet.get_audit_head();
+
+    setup.wallet.deposit(&1000, &0);
+    head = replay_audit_events(&env, &wallet, head);
+
+    setup.wallet.set_round_up(&campaign, &10, &100);
+    head = replay_audit_events(&env, &wallet, head);
+
+    setup.wallet.pay(&setup.provider, &93);
+    head = replay_audit_events(&env, &wallet, head);
+
+    setup.wallet.block_provider(&Address::generate(&env));
+    head = replay_audit_events(&env, &wallet, head);
+
+    setup.wallet.propose_config(&ProtocolAddresses {
+        registry: setup.registry.address.clone(),
+        usdc_token: setup.usdc.address.clone(),
+        defindex_vault: setup.vault.address.clone(),
+    });
+    head = replay_audit_events(&env, &wallet, head);
+
+    assert_ne!(head, BytesN::from_array(&env, &[0; 32]));
+    assert_eq!(setup.wallet.get_audit_head(), head);
+}
+
+#[test]
+fn test_audit_chain_covers_deferred_vault_switch() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    let wallet = setup.wallet.address.clone();
+    let new_registry = env.register(ProviderRegistry, (&Address::generate(&env),));
+    ProviderRegistryClient::new(&env, &new_registry).add_provider(&setup.provider);
+    let new_vault = env.register(MockVault, (&setup.usdc.address,));
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    setup.config.propose_addresses(&ProtocolAddresses {
+        registry: new_registry.clone(),
+        usdc_token: setup.usdc.address.clone(),
+        defindex_vault: new_vault,
+    });
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    setup.config.execute_addresses();
+    let before = setup.wallet.get_audit_head();
+
+    // Adopting only the registry is recorded in the chain
+    setup.wallet.sync_config();
+    let mut head = replay_audit_events(&env, &wallet, before.clone());
+    assert_ne!(head, before);
+    assert_eq!(setup.wallet.get_audit_head(), head);
+
+    setup.wallet.pay(&setup.provider, &1000);
+    head = replay_audit_events(&env, &wallet, head);
+
+    setup.wallet.sync_config();
+    head = replay_audit_events(&env, &wallet, head);
+    assert_eq!(setup.wallet.get_audit_head(), head);
+}
+
+#[test]
+fn test_audit_head_unchanged_by_views() {
+    let env = Env::default();
+    let setup = setup_wallet(&env);
+    fund_wallet(&setup, 1000);
+
+    setup.wallet.deposit(&1000, &0);
+    let head = setup.wallet.get_audit_head();
+
+    setup.wallet.wallet_summary();
+    setup.wallet.get_history(&0, &10);
+    assert_eq!(setup.wallet.get_audit_head(), head);
+}
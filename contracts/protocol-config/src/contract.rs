@@ -0,0 +1,386 @@
+//! ProtocolConfig Contract
+//!
+//! Shared configuration contract consulted by every HealthAidWallet.
+//! Holds the provider registry, USDC token and DeFindex vault addresses, which
+//! the admin can only change through a timelocked proposal, the platform fee
+//! charged on payments, the wallets deployed by the platform, and the
+//! emergency guardian role, which can pause deposits, payments or both across
+//! all wallets when the vault or the USDC token has an incident.
+
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, panic_with_error, symbol_short, Address,
+    Env, Symbol, Vec,
+};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DataKey {
+    Admin,
+    Guardian,
+    DepositsPaused,
+    PaymentsPaused,
+    Addresses,
+    Version,
+    PendingAddresses,
+    FeeConfig,
+    ContributionLimits,
+    Wallet(Address),
+}
+
+/// Delay between proposing and executing an address update, in seconds
+pub const UPDATE_TIMELOCK: u64 = 2 * 24 * 60 * 60;
+
+/// Dependencies shared by every wallet
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProtocolAddresses {
+    pub registry: Address,
+    pub usdc_token: Address,
+    pub defindex_vault: Address,
+}
+
+/// Upper bound on the percentage fee, in basis points
+pub const MAX_FEE_BPS: u32 = 500;
+
+/// Platform fee charged on wallet payments
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeConfig {
+    /// Address receiving the fees
+    pub treasury: Address,
+    /// Percentage fee, in basis points of the payment amount
+    pub bps: u32,
+    /// Flat fee added to every payment
+    pub flat: i128,
+    /// Maximum fee charged on a single payment, if any
+    pub max_fee: Option<i128>,
+    /// Provider categories exempt from the fee
+    pub exempt_categories: Vec<Symbol>,
+}
+
+/// Yearly caps on deposits into each wallet, per calendar year
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContributionLimits {
+    /// Cap on the owner's own deposits, if any
+    pub owner_limit: Option<i128>,
+    /// Cap on deposits by third-party funders combined, if any
+    pub third_party_limit: Option<i128>,
+}
+
+/// Address update waiting for its timelock to elapse
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingAddressUpdate {
+    pub addresses: ProtocolAddresses,
+    pub executable_at: u64,
+}
+
+/// Which wallet operations a pause applies to
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PauseScope {
+    Deposits,
+    Payments,
+    All,
+}
+
+#[contract]
+pub struct ProtocolConfig;
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum ProtocolConfigError {
+    Unauthorized = 3001,
+    NoPendingUpdate = 3002,
+    TimelockNotElapsed = 3003,
+    InvalidFee = 3004,
+    InvalidLimit = 3005,
+}
+
+#[contractimpl]
+impl ProtocolConfig {
+    /// Initialize the protocol configuration contract
+    ///
+    /// # Arguments
+    /// * `admin` - The address allowed to manage the configuration
+    /// * `guardian` - The emergency role allowed to pause wallets
+    /// * `addresses` - The registry, USDC token and DeFindex vault addresses
+    pub fn __constructor(e: &Env, admin: Address, guardian: Address, addresses: ProtocolAddresses) {
+        e.storage().instance().set(&DataKey::Admin, &admin);
+        e.storage().instance().set(&DataKey::Guardian, &guardian);
+        e.storage().instance().set(&DataKey::Addresses, &addresses);
+        e.storage().instance().set(&DataKey::Version, &1u32);
+    }
+
+    /// Get the admin address
+    pub fn get_admin(e: &Env) -> Address {
+        e.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Admin should be set")
+    }
+
+    /// Get the guardian address
+    pub fn get_guardian(e: &Env) -> Address {
+        e.storage()
+            .instance()
+            .get(&DataKey::Guardian)
+            .expect("Guardian should be set")
+    }
+
+    /// Replace the guardian
+    ///
+    /// # Arguments
+    /// * `guardian` - The new emergency role
+    pub fn set_guardian(e: &Env, guardian: Address) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        e.storage().instance().set(&DataKey::Guardian, &guardian);
+    }
+
+    /// Get the addresses currently in effect
+    pub fn get_addresses(e: &Env) -> ProtocolAddresses {
+        e.storage()
+            .instance()
+            .get(&DataKey::Addresses)
+            .expect("Addresses should be set")
+    }
+
+    /// Get the configuration version, bumped on every executed address update
+    ///
+    /// Wallets cache the addresses and only re-read them when this changes.
+    pub fn get_version(e: &Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&DataKey::Version)
+            .expect("Version should be set")
+    }
+
+    /// Get the address update waiting for its timelock, if any
+    pub fn get_pending_addresses(e: &Env) -> Option<PendingAddressUpdate> {
+        e.storage().instance().get(&DataKey::PendingAddresses)
+    }
+
+    /// Queue an address update, executable after `UPDATE_TIMELOCK`
+    ///
+    /// Replaces any update already pending. Moving to a new vault does not move
+    /// the wallets' existing positions; the timelock gives owners time to react.
+    ///
+    /// # Arguments
+    /// * `addresses` - The new registry, USDC token and DeFindex vault addresses
+    pub fn propose_addresses(e: &Env, addresses: ProtocolAddresses) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        let pending = PendingAddressUpdate {
+            addresses,
+            executable_at: e.ledger().timestamp() + UPDATE_TIMELOCK,
+        };
+        e.storage()
+            .instance()
+            .set(&DataKey::PendingAddresses, &pending);
+        e.events()
+            .publish((symbol_short!("proposed"),), pending.executable_at);
+    }
+
+    /// Drop the pending address update
+    pub fn cancel_addresses(e: &Env) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        if Self::get_pending_addresses(e).is_none() {
+            panic_with_error!(e, ProtocolConfigError::NoPendingUpdate);
+        }
+
+        e.storage().instance().remove(&DataKey::PendingAddresses);
+        e.events().publish((symbol_short!("cancelled"),), ());
+    }
+
+    /// Apply the pending address update once its timelock elapsed
+    pub fn execute_addresses(e: &Env) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        let pending = Self::get_pending_addresses(e).unwrap_or_else(|| {
+            panic_with_error!(e, ProtocolConfigError::NoPendingUpdate);
+        });
+        if e.ledger().timestamp() < pending.executable_at {
+            panic_with_error!(e, ProtocolConfigError::TimelockNotElapsed);
+        }
+
+        let version = Self::get_version(e) + 1;
+        e.storage()
+            .instance()
+            .set(&DataKey::Addresses, &pending.addresses);
+        e.storage().instance().set(&DataKey::Version, &version);
+        e.storage().instance().remove(&DataKey::PendingAddresses);
+        e.events().publish((symbol_short!("executed"),), version);
+    }
+
+    /// Get the platform fee charged on payments, if any
+    pub fn get_fee_config(e: &Env) -> Option<FeeConfig> {
+        e.storage().instance().get(&DataKey::FeeConfig)
+    }
+
+    /// Set the platform fee charged on payments
+    ///
+    /// # Arguments
+    /// * `fee_config` - The fee parameters and treasury address
+    pub fn set_fee_config(e: &Env, fee_config: FeeConfig) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        let invalid_cap = fee_config.max_fee.is_some_and(|max_fee| max_fee < 0);
+        if fee_config.bps > MAX_FEE_BPS || fee_config.flat < 0 || invalid_cap {
+            panic_with_error!(e, ProtocolConfigError::InvalidFee);
+        }
+
+        e.storage().instance().set(&DataKey::FeeConfig, &fee_config);
+        e.events().publish((symbol_short!("fee_set"),), fee_config);
+    }
+
+    /// Remove the platform fee
+    pub fn remove_fee_config(e: &Env) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        e.storage().instance().remove(&DataKey::FeeConfig);
+        e.events().publish((symbol_short!("fee_unset"),), ());
+    }
+
+    /// Get the yearly contribution caps, if any
+    pub fn get_contribution_limits(e: &Env) -> Option<ContributionLimits> {
+        e.storage().instance().get(&DataKey::ContributionLimits)
+    }
+
+    /// Set the yearly contribution caps enforced by every wallet
+    ///
+    /// # Arguments
+    /// * `limits` - The caps for owner and third-party deposits
+    pub fn set_contribution_limits(e: &Env, limits: ContributionLimits) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        let negative = |limit: Option<i128>| limit.is_some_and(|limit| limit < 0);
+        if negative(limits.owner_limit) || negative(limits.third_party_limit) {
+            panic_with_error!(e, ProtocolConfigError::InvalidLimit);
+        }
+
+        e.storage()
+            .instance()
+            .set(&DataKey::ContributionLimits, &limits);
+        e.events().publish((symbol_short!("limit_set"),), limits);
+    }
+
+    /// Remove the yearly contribution caps
+    pub fn remove_contribution_limits(e: &Env) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        e.storage().instance().remove(&DataKey::ContributionLimits);
+        e.events().publish((symbol_short!("limit_del"),), ());
+    }
+
+    /// Check if an address is a wallet deployed by the platform
+    ///
+    /// Wallets only migrate their funds to registered wallets.
+    pub fn is_registered_wallet(e: &Env, wallet: Address) -> bool {
+        e.storage()
+            .persistent()
+            .get(&DataKey::Wallet(wallet))
+            .unwrap_or(false)
+    }
+
+    /// Register a wallet deployed by the platform
+    ///
+    /// # Arguments
+    /// * `wallet` - The address of the deployed wallet
+    pub fn register_wallet(e: &Env, wallet: Address) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        e.storage()
+            .persistent()
+            .set(&DataKey::Wallet(wallet.clone()), &true);
+        e.events().publish((symbol_short!("wlt_add"), wallet), ());
+    }
+
+    /// Remove a wallet from the registered wallets
+    ///
+    /// # Arguments
+    /// * `wallet` - The address of the wallet
+    pub fn unregister_wallet(e: &Env, wallet: Address) {
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        e.storage()
+            .persistent()
+            .remove(&DataKey::Wallet(wallet.clone()));
+        e.events().publish((symbol_short!("wlt_del"), wallet), ());
+    }
+
+    /// Pause wallet operations globally
+    ///
+    /// # Arguments
+    /// * `caller` - The guardian or the admin
+    /// * `scope` - The operations to pause
+    pub fn pause(e: &Env, caller: Address, scope: PauseScope) {
+        Self::require_emergency_role(e, &caller);
+        Self::set_paused(e, scope, true);
+        e.events().publish((symbol_short!("paused"), caller), scope);
+    }
+
+    /// Resume wallet operations globally
+    ///
+    /// # Arguments
+    /// * `caller` - The guardian or the admin
+    /// * `scope` - The operations to resume
+    pub fn unpause(e: &Env, caller: Address, scope: PauseScope) {
+        Self::require_emergency_role(e, &caller);
+        Self::set_paused(e, scope, false);
+        e.events()
+            .publish((symbol_short!("unpaused"), caller), scope);
+    }
+
+    /// Check if deposits are paused across all wallets
+    pub fn is_deposits_paused(e: &Env) -> bool {
+        e.storage()
+            .instance()
+            .get(&DataKey::DepositsPaused)
+            .unwrap_or(false)
+    }
+
+    /// Check if payments are paused across all wallets
+    pub fn is_payments_paused(e: &Env) -> bool {
+        e.storage()
+            .instance()
+            .get(&DataKey::PaymentsPaused)
+            .unwrap_or(false)
+    }
+}
+
+impl ProtocolConfig {
+    fn require_emergency_role(e: &Env, caller: &Address) {
+        caller.require_auth();
+
+        if *caller != Self::get_guardian(e) && *caller != Self::get_admin(e) {
+            panic_with_error!(e, ProtocolConfigError::Unauthorized);
+        }
+    }
+
+    fn set_paused(e: &Env, scope: PauseScope, paused: bool) {
+        if scope != PauseScope::Payments {
+            e.storage()
+                .instance()
+                .set(&DataKey::DepositsPaused, &paused);
+        }
+        if scope != PauseScope::Deposits {
+            e.storage()
+                .instance()
+                .set(&DataKey::PaymentsPaused, &paused);
+        }
+    }
+}
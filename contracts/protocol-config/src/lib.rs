@@ -0,0 +1,10 @@
+#![no_std]
+#![allow(dead_code)]
+
+mod contract;
+mod test;
+
+pub use crate::contract::{
+    ContributionLimits, FeeConfig, PauseScope, PendingAddressUpdate, ProtocolAddresses,
+    ProtocolConfig, ProtocolConfigClient, MAX_FEE_BPS, UPDATE_TIMELOCK,
+};
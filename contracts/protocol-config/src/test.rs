@@ -0,0 +1,341 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{
+    testutils::{Address as TestAddress, Ledger, MockAuth, MockAuthInvoke},
+    vec, Address, Env, IntoVal, Symbol,
+};
+
+fn create_test_addresses(env: &Env) -> (Address, Address, Address) {
+    let admin = Address::generate(env);
+    let guardian = Address::generate(env);
+    let unauthorized = Address::generate(env);
+    (admin, guardian, unauthorized)
+}
+
+fn create_protocol_addresses(env: &Env) -> ProtocolAddresses {
+    ProtocolAddresses {
+        registry: Address::generate(env),
+        usdc_token: Address::generate(env),
+        defindex_vault: Address::generate(env),
+    }
+}
+
+#[test]
+fn test_constructor() {
+    let env = Env::default();
+    let (admin, guardian, _) = create_test_addresses(&env);
+
+    let addresses = create_protocol_addresses(&env);
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(client.get_guardian(), guardian);
+    assert!(!client.is_deposits_paused());
+    assert!(!client.is_payments_paused());
+    assert_eq!(client.get_addresses(), addresses);
+    assert_eq!(client.get_version(), 1);
+    assert_eq!(client.get_pending_addresses(), None);
+}
+
+#[test]
+fn test_pause_scopes() {
+    let env = Env::default();
+    let (admin, guardian, _) = create_test_addresses(&env);
+
+    let addresses = create_protocol_addresses(&env);
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.pause(&guardian, &PauseScope::Deposits);
+    assert!(client.is_deposits_paused());
+    assert!(!client.is_payments_paused());
+
+    client.pause(&guardian, &PauseScope::All);
+    assert!(client.is_deposits_paused());
+    assert!(client.is_payments_paused());
+
+    client.unpause(&admin, &PauseScope::Deposits);
+    assert!(!client.is_deposits_paused());
+    assert!(client.is_payments_paused());
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #3001)")]
+fn test_pause_unauthorized() {
+    let env = Env::default();
+    let (admin, guardian, unauthorized) = create_test_addresses(&env);
+
+    let addresses = create_protocol_addresses(&env);
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+
+    env.mock_auths(&[MockAuth {
+        address: &unauthorized,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "pause",
+            args: (&unauthorized, PauseScope::All).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+
+    client.pause(&unauthorized, &PauseScope::All);
+}
+
+#[test]
+fn test_set_guardian() {
+    let env = Env::default();
+    let (admin, guardian, new_guardian) = create_test_addresses(&env);
+
+    let addresses = create_protocol_addresses(&env);
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+
+    env.mock_auths(&[MockAuth {
+        address: &admin,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "set_guardian",
+            args: (&new_guardian,).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    client.set_guardian(&new_guardian);
+
+    assert_eq!(client.get_guardian(), new_guardian);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_set_guardian_unauthorized() {
+    let env = Env::default();
+    let (admin, guardian, unauthorized) = create_test_addresses(&env);
+
+    let addresses = create_protocol_addresses(&env);
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+
+    env.mock_auths(&[MockAuth {
+        address: &unauthorized,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "set_guardian",
+            args: (&unauthorized,).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    client.set_guardian(&unauthorized);
+}
+
+#[test]
+fn test_execute_addresses_after_timelock() {
+    let env = Env::default();
+    let (admin, guardian, _) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+    let new_addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.propose_addresses(&new_addresses);
+    let pending = client.get_pending_addresses().unwrap();
+    assert_eq!(pending.addresses, new_addresses);
+    assert_eq!(pending.executable_at, UPDATE_TIMELOCK);
+
+    // Nothing changes until the update is executed
+    assert_eq!(client.get_addresses(), addresses);
+
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    client.execute_addresses();
+
+    assert_eq!(client.get_addresses(), new_addresses);
+    assert_eq!(client.get_version(), 2);
+    assert_eq!(client.get_pending_addresses(), None);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #3003)")]
+fn test_execute_addresses_before_timelock() {
+    let env = Env::default();
+    let (admin, guardian, _) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.propose_addresses(&create_protocol_addresses(&env));
+    env.ledger()
+        .with_mut(|li| li.timestamp += UPDATE_TIMELOCK - 1);
+    client.execute_addresses();
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #3002)")]
+fn test_execute_addresses_after_cancel() {
+    let env = Env::default();
+    let (admin, guardian, _) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.propose_addresses(&create_protocol_addresses(&env));
+    client.cancel_addresses();
+
+    env.ledger().with_mut(|li| li.timestamp += UPDATE_TIMELOCK);
+    client.execute_addresses();
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_propose_addresses_unauthorized() {
+    let env = Env::default();
+    let (admin, guardian, unauthorized) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+
+    env.mock_auths(&[MockAuth {
+        address: &unauthorized,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "propose_addresses",
+            args: (&addresses,).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    client.propose_addresses(&addresses);
+}
+
+#[test]
+fn test_set_fee_config() {
+    let env = Env::default();
+    let (admin, guardian, treasury) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    assert_eq!(client.get_fee_config(), None);
+
+    let fee_config = FeeConfig {
+        treasury,
+        bps: 50,
+        flat: 10,
+        max_fee: Some(1_000),
+        exempt_categories: vec![&env, Symbol::new(&env, "pharmacy")],
+    };
+    client.set_fee_config(&fee_config);
+    assert_eq!(client.get_fee_config(), Some(fee_config));
+
+    client.remove_fee_config();
+    assert_eq!(client.get_fee_config(), None);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #3004)")]
+fn test_set_fee_config_above_max_bps() {
+    let env = Env::default();
+    let (admin, guardian, treasury) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.set_fee_config(&FeeConfig {
+        treasury,
+        bps: MAX_FEE_BPS + 1,
+        flat: 0,
+        max_fee: None,
+        exempt_categories: vec![&env],
+    });
+}
+
+#[test]
+fn test_set_contribution_limits() {
+    let env = Env::default();
+    let (admin, guardian, _) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    assert_eq!(client.get_contribution_limits(), None);
+
+    let limits = ContributionLimits {
+        owner_limit: Some(4_150),
+        third_party_limit: None,
+    };
+    client.set_contribution_limits(&limits);
+    assert_eq!(client.get_contribution_limits(), Some(limits));
+
+    client.remove_contribution_limits();
+    assert_eq!(client.get_contribution_limits(), None);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #3005)")]
+fn test_set_contribution_limits_negative() {
+    let env = Env::default();
+    let (admin, guardian, _) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.set_contribution_limits(&ContributionLimits {
+        owner_limit: Some(-1),
+        third_party_limit: None,
+    });
+}
+
+#[test]
+fn test_register_wallet() {
+    let env = Env::default();
+    let (admin, guardian, wallet) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    assert!(!client.is_registered_wallet(&wallet));
+    client.register_wallet(&wallet);
+    assert!(client.is_registered_wallet(&wallet));
+
+    client.unregister_wallet(&wallet);
+    assert!(!client.is_registered_wallet(&wallet));
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_register_wallet_unauthorized() {
+    let env = Env::default();
+    let (admin, guardian, unauthorized) = create_test_addresses(&env);
+    let addresses = create_protocol_addresses(&env);
+
+    let contract_id = env.register(ProtocolConfig, (&admin, &guardian, &addresses));
+    let client = ProtocolConfigClient::new(&env, &contract_id);
+
+    env.mock_auths(&[MockAuth {
+        address: &unauthorized,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "register_wallet",
+            args: (&unauthorized,).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    client.register_wallet(&unauthorized);
+}
@@ -6,7 +6,7 @@
 
 use soroban_sdk::{
     contract, contracterror, contractimpl, contracttype, panic_with_error, symbol_short, Address,
-    Env, Symbol,
+    BytesN, Env, Symbol,
 };
 
 // Storage keys
@@ -18,6 +18,9 @@ pub const PROVIDERS: Symbol = symbol_short!("PROVIDERS");
 pub enum DataKey {
     Admin,
     Providers(Address),
+    Category(Address),
+    Arbiter,
+    SigningKey(Address),
 }
 
 #[contract]
@@ -85,7 +88,91 @@ impl ProviderRegistry {
 
         e.storage()
             .persistent()
-            .remove(&DataKey::Providers(provider));
+            .remove(&DataKey::Providers(provider.clone()));
+        e.storage()
+            .persistent()
+            .remove(&DataKey::Category(provider.clone()));
+        e.storage()
+            .persistent()
+            .remove(&DataKey::SigningKey(provider));
+    }
+
+    /// Set the category of an approved healthcare provider
+    ///
+    /// # Arguments
+    /// * `provider` - The address of the healthcare provider
+    /// * `category` - The provider category, e.g. `hospital`, `lab` or `pharmacy`
+    pub fn set_provider_category(e: &Env, provider: Address, category: Symbol) {
+        // Verify that the caller is the admin
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        if !Self::is_provider(e, provider.clone()) {
+            panic_with_error!(e, ProviderRegistryError::ProviderNotFound);
+        }
+
+        e.storage()
+            .persistent()
+            .set(&DataKey::Category(provider), &category);
+    }
+
+    /// Get the category of a healthcare provider
+    ///
+    /// # Arguments
+    /// * `provider` - The address of the healthcare provider
+    ///
+    /// # Returns
+    /// * `Option<Symbol>` - The provider category, if one was set
+    pub fn get_provider_category(e: &Env, provider: Address) -> Option<Symbol> {
+        e.storage().persistent().get(&DataKey::Category(provider))
+    }
+
+    /// Register the ed25519 key a provider signs payment requests with
+    ///
+    /// # Arguments
+    /// * `provider` - The address of the healthcare provider, must authorize
+    /// * `key` - The ed25519 public key
+    pub fn set_provider_key(e: &Env, provider: Address, key: BytesN<32>) {
+        provider.require_auth();
+
+        if !Self::is_provider(e, provider.clone()) {
+            panic_with_error!(e, ProviderRegistryError::ProviderNotFound);
+        }
+
+        e.storage()
+            .persistent()
+            .set(&DataKey::SigningKey(provider), &key);
+    }
+
+    /// Get the ed25519 key a provider signs payment requests with
+    ///
+    /// # Arguments
+    /// * `provider` - The address of the healthcare provider
+    ///
+    /// # Returns
+    /// * `Option<BytesN<32>>` - The provider's public key, if one was registered
+    pub fn get_provider_key(e: &Env, provider: Address) -> Option<BytesN<32>> {
+        e.storage().persistent().get(&DataKey::SigningKey(provider))
+    }
+
+    /// Appoint the arbiter who resolves disputed escrowed payments
+    ///
+    /// # Arguments
+    /// * `arbiter` - The address of the arbiter
+    pub fn set_arbiter(e: &Env, arbiter: Address) {
+        // Verify that the caller is the admin
+        let admin = Self::get_admin(e);
+        admin.require_auth();
+
+        e.storage().instance().set(&DataKey::Arbiter, &arbiter);
+    }
+
+    /// Get the arbiter who resolves disputed escrowed payments
+    ///
+    /// # Returns
+    /// * `Option<Address>` - The arbiter, if one was appointed
+    pub fn get_arbiter(e: &Env) -> Option<Address> {
+        e.storage().instance().get(&DataKey::Arbiter)
     }
 
     /// Check if an address is an approved healthcare provider
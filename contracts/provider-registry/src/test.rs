@@ -3,7 +3,7 @@
 use super::*;
 use soroban_sdk::{
     testutils::{Address as TestAddress, MockAuth, MockAuthInvoke},
-    Address, Env, IntoVal,
+    Address, BytesN, Env, IntoVal, Symbol,
 };
 
 fn create_test_addresses(env: &Env) -> (Address, Address, Address) {
@@ -199,3 +199,84 @@ fn test_is_provider() {
     assert!(client.is_provider(&provider1));
     assert!(!client.is_provider(&provider2));
 }
+
+#[test]
+fn test_set_provider_category() {
+    let env = Env::default();
+    let (admin, provider, _) = create_test_addresses(&env);
+
+    let contract_id = env.register(ProviderRegistry, (&admin,));
+    let client = ProviderRegistryClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.add_provider(&provider);
+    assert_eq!(client.get_provider_category(&provider), None);
+
+    let pharmacy = Symbol::new(&env, "pharmacy");
+    client.set_provider_category(&provider, &pharmacy);
+    assert_eq!(client.get_provider_category(&provider), Some(pharmacy));
+
+    // Removing the provider clears its category
+    client.remove_provider(&provider);
+    assert_eq!(client.get_provider_category(&provider), None);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #2003)")]
+fn test_set_provider_category_not_found() {
+    let env = Env::default();
+    let (admin, provider, _) = create_test_addresses(&env);
+
+    let contract_id = env.register(ProviderRegistry, (&admin,));
+    let client = ProviderRegistryClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.set_provider_category(&provider, &Symbol::new(&env, "lab"));
+}
+
+#[test]
+fn test_set_arbiter() {
+    let env = Env::default();
+    let (admin, _, _) = create_test_addresses(&env);
+    let arbiter = Address::generate(&env);
+
+    let contract_id = env.register(ProviderRegistry, (&admin,));
+    let client = ProviderRegistryClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    assert_eq!(client.get_arbiter(), None);
+    client.set_arbiter(&arbiter);
+    assert_eq!(client.get_arbiter(), Some(arbiter));
+}
+
+#[test]
+fn test_set_provider_key() {
+    let env = Env::default();
+    let (admin, provider, _) = create_test_addresses(&env);
+    let key = BytesN::from_array(&env, &[7; 32]);
+
+    let contract_id = env.register(ProviderRegistry, (&admin,));
+    let client = ProviderRegistryClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.add_provider(&provider);
+    client.set_provider_key(&provider, &key);
+    assert_eq!(client.get_provider_key(&provider), Some(key));
+
+    // Removing the provider clears its key
+    client.remove_provider(&provider);
+    assert_eq!(client.get_provider_key(&provider), None);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #2003)")]
+fn test_set_provider_key_not_found() {
+    let env = Env::default();
+    let (admin, provider, _) = create_test_addresses(&env);
+
+    let contract_id = env.register(ProviderRegistry, (&admin,));
+    let client = ProviderRegistryClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    client.set_provider_key(&provider, &BytesN::from_array(&env, &[7; 32]));
+}
@@ -1,7 +1,6 @@
 import {
   Account,
   Address,
-  Asset,
   Keypair,
   nativeToScVal,
   Operation,
@@ -86,14 +85,7 @@ Setup of Stellar Smart Wallet
   console.log("WASM uploaded with hash:", wasmHash);
   console.log("Deploying contract...");
 
-  const REGISTRY = process.env.REGISTRY_ADDRESS!;
-  const USDC = process.env.USDC_TOKEN_ADDRESS!;
-  const DEFINDEX = process.env.DEFINDEX_CONTRACT!;
-
-  // Se USDC começar com 'C', assume que é o contractId; se começar com 'G', assume issuer
-  const usdcContractId = USDC.startsWith("C")
-    ? USDC
-    : new Asset("USDC", USDC).contractId(stellarNetwork);
+  const PROTOCOL_CONFIG = process.env.PROTOCOL_CONFIG_ADDRESS!;
 
   const toAddr = (s: string) => nativeToScVal(new Address(s), { type: "address" });
 
@@ -106,12 +98,7 @@ Setup of Stellar Smart Wallet
         address: new Address(sourceKeypair.publicKey()),
         wasmHash: Buffer.from(wasmHash!, "hex"),
         salt: generateRandomSalt(),
-        constructorArgs: [
-          toAddr(userAddress),
-          toAddr(REGISTRY),
-          toAddr(usdcContractId),
-          toAddr(DEFINDEX),
-        ],
+        constructorArgs: [toAddr(userAddress), toAddr(PROTOCOL_CONFIG)],
       }),
     )
     .setTimeout(90)
@@ -132,5 +119,30 @@ Setup of Stellar Smart Wallet
   ).toString();
 
   console.log("Contract deployed with ID:", contractId);
+
+  // Wallets can only migrate to wallets registered in the protocol config.
+  // The admin key must be the protocol config's admin.
+  console.log("Registering wallet in protocol config...");
+  const registerTx = new TransactionBuilder(issuerAccount, {
+    fee: inclusionFee.toString(),
+    networkPassphrase: stellarNetwork,
+  })
+    .addOperation(
+      Operation.invokeContractFunction({
+        contract: PROTOCOL_CONFIG,
+        function: "register_wallet",
+        args: [toAddr(contractId)],
+      }),
+    )
+    .setTimeout(90)
+    .build();
+
+  const registerTxPrep = await rpc.prepareTransaction(registerTx);
+
+  registerTxPrep.sign(sourceKeypair);
+
+  await sendTransaction(registerTxPrep);
+
+  console.log("Wallet registered:", contractId);
   return contractId;
 }
\ No newline at end of file